@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Result};
+use async_channel::Receiver;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    data_storages::data_storages::{from_json, SchemaType, SchemaTypeWithValue},
+    utils::{load_json, save_json},
+};
+
+/// value with its type, which is lost in json.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TypedValue {
+    #[serde(rename = "type")]
+    pub type_: SchemaType,
+    pub value: Value,
+}
+
+impl TypedValue {
+    pub fn new(value: &SchemaTypeWithValue) -> Self {
+        TypedValue {
+            type_: value.schema_type(),
+            value: value.to_json(),
+        }
+    }
+
+    pub fn value(&self) -> Result<SchemaTypeWithValue> {
+        from_json(&self.value)
+            .cast(&self.type_)
+            .ok_or_else(|| anyhow!("invalid value {} of {:?}", self.value, self.type_))
+    }
+}
+
+/// options of a part of source and the cursor after its last written chunk, None if no chunk
+/// written yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartCheckpoint {
+    pub options: HashMap<String, String>,
+    pub cursor: Option<Vec<TypedValue>>,
+}
+
+/// progress of a chunk trans, it's saved after every written chunk to resume the trans.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub fingerprint: String,
+    pub parts: Vec<PartCheckpoint>,
+}
+
+/// chunk written by sink, chunks of a part are numbered from 0 in the order of read.
+pub struct Ack {
+    pub part: usize,
+    pub seq: u64,
+    pub cursor: Option<SchemaTypeWithValue>,
+}
+
+/// stable hash(FNV-1a) of fields in hex.
+pub fn fingerprint(fields: &[&str]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for field in fields {
+        // fields are separated by 0, e.g. ["ab", "c"] and ["a", "bc"] differ.
+        for byte in field.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+// composite cursors are saved as one typed value per column.
+fn encode_cursor(cursor: &SchemaTypeWithValue) -> Vec<TypedValue> {
+    let values = match cursor {
+        SchemaTypeWithValue::List(values) => values.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+    values.iter().map(TypedValue::new).collect()
+}
+
+impl PartCheckpoint {
+    pub fn new(options: HashMap<String, String>) -> Self {
+        PartCheckpoint {
+            options,
+            cursor: None,
+        }
+    }
+
+    /// cursor to resume the part from, None to read it from the beginning.
+    pub fn cursor(&self) -> Result<Option<SchemaTypeWithValue>> {
+        let Some(values) = &self.cursor else {
+            return Ok(None);
+        };
+        let mut values = values
+            .iter()
+            .map(TypedValue::value)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(if values.len() == 1 {
+            values.swap_remove(0)
+        } else {
+            SchemaTypeWithValue::List(values)
+        }))
+    }
+}
+
+impl Checkpoint {
+    pub fn load(path: &str) -> Result<Self> {
+        load_json(path)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        save_json(self, path)
+    }
+}
+
+/// save checkpoint into `path` whenever a part advances until all acks are received. Chunks may
+/// be written out of order by several sinks, the cursor of a part only moves to a chunk after all
+/// chunks before it are written.
+pub async fn track(path: String, mut checkpoint: Checkpoint, acks: Receiver<Ack>) -> Result<()> {
+    let mut next_seqs = vec![0; checkpoint.parts.len()];
+    let mut pending = vec![BTreeMap::new(); checkpoint.parts.len()];
+    checkpoint.save(&path)?;
+    while let Ok(ack) = acks.recv().await {
+        pending[ack.part].insert(ack.seq, ack.cursor);
+        let mut advanced = false;
+        while let Some(cursor) = pending[ack.part].remove(&next_seqs[ack.part]) {
+            next_seqs[ack.part] += 1;
+            if let Some(cursor) = cursor {
+                checkpoint.parts[ack.part].cursor = Some(encode_cursor(&cursor));
+                advanced = true;
+            }
+        }
+        if advanced {
+            checkpoint.save(&path)?;
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::data_storages::data_storages::{Schema, SchemaField, SchemaType};
+use crate::data_storages::data_storages::{CoercionPolicy, Schema, SchemaField, SchemaType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -12,6 +12,9 @@ pub struct DataStorageConfig {
 pub struct Config {
     pub data_storages: HashMap<String, DataStorageConfig>,
     pub schemas: HashMap<String, Schema>,
+    /// how values are cast to sink schema, could be overwritten by `coercion` in extra of field.
+    #[serde(default)]
+    pub coercion: CoercionPolicy,
 }
 
 impl Config {
@@ -32,6 +35,7 @@ impl Config {
                     extra: HashMap::new(),
                 }]),
             )]),
+            coercion: CoercionPolicy::Safe,
         }
     }
 }
@@ -0,0 +1,804 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use arrow::{
+    array::{
+        Array, ArrayRef, BinaryArray, BinaryBuilder, BooleanArray, BooleanBuilder, Date32Array,
+        Date32Builder, Decimal128Array, Decimal128Builder, FixedSizeBinaryArray,
+        FixedSizeBinaryBuilder, Float32Array, Float32Builder, Float64Array, Float64Builder,
+        Int16Array, Int32Array, Int32Builder, Int64Array, Int64Builder, Int8Array,
+        IntervalMonthDayNanoArray, IntervalMonthDayNanoBuilder, LargeBinaryArray, LargeListArray,
+        LargeStringArray, ListArray, NullArray, StringArray, StringBuilder, StructArray,
+        Time32MillisecondArray, Time32SecondArray, Time64MicrosecondArray,
+        Time64MicrosecondBuilder, Time64NanosecondArray, TimestampMicrosecondArray,
+        TimestampMicrosecondBuilder, TimestampMillisecondArray, TimestampNanosecondArray,
+        TimestampNanosecondBuilder, TimestampSecondArray, UInt16Array, UInt32Array, UInt8Array,
+    },
+    buffer::{NullBuffer, OffsetBuffer},
+    datatypes::{
+        DataType, Field, IntervalMonthDayNano, IntervalUnit, Schema as ArrowSchema, SchemaRef,
+        TimeUnit,
+    },
+    record_batch::RecordBatch,
+};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
+use num_bigint::BigInt;
+use uuid::Uuid;
+
+use super::data_storages::{
+    CoercionPolicy, Column, Interval, Row, Schema, SchemaField, SchemaType, SchemaTypeWithValue,
+    TimePrecision,
+};
+
+const UTC: &str = "UTC";
+// byte width of uuid, which is stored as fixed size binary.
+const UUID_WIDTH: i32 = 16;
+const EXTENSION_NAME_KEY: &str = "ARROW:extension:name";
+const UUID_EXTENSION_NAME: &str = "arrow.uuid";
+const JSON_EXTENSION_NAME: &str = "arrow.json";
+// max precision of arrow decimal128.
+const DECIMAL128_MAX_PRECISION: u16 = 38;
+// scale of unbounded decimals, which are stored in the widest decimal128 like spark does.
+const UNBOUNDED_DECIMAL_SCALE: i16 = 18;
+
+// precision and scale of decimal in arrow.
+fn arrow_decimal(precision: u16, scale: i16) -> (u16, i16) {
+    match (precision, scale) {
+        (0, 0) => (DECIMAL128_MAX_PRECISION, UNBOUNDED_DECIMAL_SCALE),
+        decimal => decimal,
+    }
+}
+
+fn unix_epoch_date() -> NaiveDate {
+    DateTime::UNIX_EPOCH.date_naive()
+}
+
+fn time_unit(precision: &TimePrecision) -> TimeUnit {
+    match precision {
+        TimePrecision::Micro => TimeUnit::Microsecond,
+        TimePrecision::Nano => TimeUnit::Nanosecond,
+    }
+}
+
+// units coarser than microsecond are widened to microsecond.
+fn time_precision(unit: &TimeUnit) -> TimePrecision {
+    match unit {
+        TimeUnit::Nanosecond => TimePrecision::Nano,
+        _ => TimePrecision::Micro,
+    }
+}
+
+fn schema_type_to_arrow(type_: &SchemaType) -> Result<DataType> {
+    Ok(match type_ {
+        SchemaType::String => DataType::Utf8,
+        SchemaType::Int32 => DataType::Int32,
+        SchemaType::Int64 => DataType::Int64,
+        SchemaType::Binary => DataType::Binary,
+        SchemaType::Boolean => DataType::Boolean,
+        SchemaType::Timestamp(precision) => DataType::Timestamp(time_unit(precision), None),
+        SchemaType::Timestamptz(precision) => {
+            DataType::Timestamp(time_unit(precision), Some(UTC.into()))
+        }
+        SchemaType::Date => DataType::Date32,
+        SchemaType::Time => DataType::Time64(TimeUnit::Microsecond),
+        SchemaType::Interval => DataType::Interval(IntervalUnit::MonthDayNano),
+        SchemaType::Double => DataType::Float64,
+        SchemaType::Float => DataType::Float32,
+        SchemaType::Decimal(precision, scale) => {
+            let (precision, scale) = arrow_decimal(*precision, *scale);
+            if precision > DECIMAL128_MAX_PRECISION {
+                return Err(anyhow!(
+                    "precision {precision} of decimal exceeds {DECIMAL128_MAX_PRECISION}, which is the max of arrow decimal128"
+                ));
+            }
+            DataType::Decimal128(u8::try_from(precision)?, i8::try_from(scale)?)
+        }
+        SchemaType::Uuid => DataType::FixedSizeBinary(UUID_WIDTH),
+        SchemaType::Json => DataType::Utf8,
+        SchemaType::List(inner) => DataType::List(Arc::new(field_to_arrow(&list_item(inner))?)),
+        SchemaType::Struct(fields) => DataType::Struct(
+            fields
+                .iter()
+                .map(field_to_arrow)
+                .collect::<Result<Vec<_>>>()?
+                .into(),
+        ),
+        SchemaType::None => DataType::Null,
+    })
+}
+
+fn arrow_type_to_schema(data_type: &DataType) -> Result<SchemaType> {
+    Ok(match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 => SchemaType::String,
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            SchemaType::Int32
+        }
+        DataType::Int64 | DataType::UInt32 => SchemaType::Int64,
+        DataType::FixedSizeBinary(UUID_WIDTH) => SchemaType::Uuid,
+        DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+            SchemaType::Binary
+        }
+        DataType::Boolean => SchemaType::Boolean,
+        DataType::Timestamp(unit, None) => SchemaType::Timestamp(time_precision(unit)),
+        DataType::Timestamp(unit, Some(_)) => SchemaType::Timestamptz(time_precision(unit)),
+        DataType::Date32 => SchemaType::Date,
+        DataType::Time32(_) | DataType::Time64(_) => SchemaType::Time,
+        DataType::Interval(IntervalUnit::MonthDayNano) => SchemaType::Interval,
+        DataType::Float64 => SchemaType::Double,
+        DataType::Float32 => SchemaType::Float,
+        DataType::Decimal128(precision, scale) => {
+            SchemaType::Decimal(u16::from(*precision), i16::from(*scale))
+        }
+        DataType::List(item) | DataType::LargeList(item) => {
+            SchemaType::List(Box::new(field_to_schema(item)?.type_))
+        }
+        DataType::Struct(fields) => SchemaType::Struct(
+            fields
+                .iter()
+                .map(|field| field_to_schema(field))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        DataType::Null => SchemaType::None,
+        unk => {
+            return Err(anyhow!(
+                "cannot convert arrow type {unk}, may not supported yet."
+            ))
+        }
+    })
+}
+
+// field of list elements.
+fn list_item(inner: &SchemaType) -> SchemaField {
+    SchemaField {
+        name: "item".to_string(),
+        type_: inner.clone(),
+        extra: HashMap::new(),
+    }
+}
+
+fn field_to_arrow(field: &SchemaField) -> Result<Field> {
+    let nullable = field.extra.get("nullable").map(String::as_str) != Some("false");
+    let mut metadata = field.extra.clone();
+    let extension = match field.type_ {
+        SchemaType::Uuid => Some(UUID_EXTENSION_NAME),
+        SchemaType::Json => Some(JSON_EXTENSION_NAME),
+        _ => None,
+    };
+    if let Some(extension) = extension {
+        metadata.insert(EXTENSION_NAME_KEY.to_string(), extension.to_string());
+    }
+    Ok(
+        Field::new(&field.name, schema_type_to_arrow(&field.type_)?, nullable)
+            .with_metadata(metadata),
+    )
+}
+
+/// the `extra` of fields are kept in arrow field metadata, fields are nullable unless
+/// `nullable=false`. Uuid and json are marked by the canonical `arrow.uuid` and `arrow.json`
+/// extensions.
+pub fn schema_to_arrow(schema: &Schema) -> Result<ArrowSchema> {
+    Ok(ArrowSchema::new(
+        schema
+            .0
+            .iter()
+            .map(field_to_arrow)
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+// json is stored as utf8 with the `arrow.json` extension.
+fn is_json_field(field: &Field) -> bool {
+    matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8)
+        && field.metadata().get(EXTENSION_NAME_KEY).map(String::as_str) == Some(JSON_EXTENSION_NAME)
+}
+
+fn field_to_schema(field: &Field) -> Result<SchemaField> {
+    let type_ = if is_json_field(field) {
+        SchemaType::Json
+    } else {
+        arrow_type_to_schema(field.data_type())?
+    };
+    let mut extra: HashMap<String, String> = field.metadata().clone();
+    extra
+        .entry("nullable".to_string())
+        .or_insert(field.is_nullable().to_string());
+    extra.extend(type_.extra());
+    Ok(SchemaField {
+        name: field.name().clone(),
+        type_,
+        extra,
+    })
+}
+
+pub fn arrow_to_schema(schema: &ArrowSchema) -> Result<Schema> {
+    Ok(Schema(
+        schema
+            .fields()
+            .iter()
+            .map(|field| field_to_schema(field))
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+fn cast_error(value: &SchemaTypeWithValue, type_: &SchemaType) -> anyhow::Error {
+    anyhow!("cannot convert value {value:?} to {type_:?}")
+}
+
+// naive timestamps are stored as if in UTC, dates are at midnight.
+fn timestamp_of(value: &SchemaTypeWithValue) -> Option<DateTime<Utc>> {
+    match value {
+        SchemaTypeWithValue::Timestamp(t) => Some(t.and_utc()),
+        SchemaTypeWithValue::Timestamptz(t) => Some(*t),
+        SchemaTypeWithValue::Date(d) => Some(d.and_time(NaiveTime::MIN).and_utc()),
+        _ => None,
+    }
+}
+
+fn timestamp_in(timestamp: DateTime<Utc>, precision: &TimePrecision) -> Option<i64> {
+    match precision {
+        TimePrecision::Micro => Some(timestamp.timestamp_micros()),
+        TimePrecision::Nano => timestamp.timestamp_nanos_opt(),
+    }
+}
+
+// unscaled value of decimal in `precision` and `scale`, None if it can not be represented
+// without rounding.
+fn decimal128_of(value: &SchemaTypeWithValue, precision: u16, scale: i16) -> Option<i128> {
+    let decimal = match value {
+        SchemaTypeWithValue::Decimal(d) => d.clone(),
+        SchemaTypeWithValue::Int32(i) => BigDecimal::from(*i),
+        SchemaTypeWithValue::Int64(i) => BigDecimal::from(*i),
+        _ => return None,
+    };
+    let rescaled = decimal.with_scale(i64::from(scale));
+    if rescaled != decimal || rescaled.digits() > u64::from(precision) {
+        return None;
+    }
+    rescaled.as_bigint_and_exponent().0.to_i128()
+}
+
+// build the array of `type_` from values, values are widened to the type if possible.
+fn build_array(values: &[&SchemaTypeWithValue], type_: &SchemaType) -> Result<ArrayRef> {
+    macro_rules! build {
+        ($builder:expr, $convert:expr) => {{
+            let mut builder = $builder;
+            for value in values.iter().copied() {
+                match value {
+                    SchemaTypeWithValue::None => builder.append_null(),
+                    value => builder
+                        .append_value($convert(value).ok_or_else(|| cast_error(value, type_))?),
+                }
+            }
+            Arc::new(builder.finish()) as ArrayRef
+        }};
+    }
+    Ok(match type_ {
+        SchemaType::String => build!(StringBuilder::new(), |v: &SchemaTypeWithValue| v.to_text()),
+        // strings are json strings, json texts are parsed into json values by normalizing rows.
+        SchemaType::Json => build!(StringBuilder::new(), |v: &SchemaTypeWithValue| Some(
+            v.to_json().to_string()
+        )),
+        SchemaType::Int32 => build!(Int32Builder::new(), |v: &SchemaTypeWithValue| match v {
+            SchemaTypeWithValue::Int32(i) => Some(*i),
+            _ => None,
+        }),
+        SchemaType::Int64 => build!(Int64Builder::new(), |v: &SchemaTypeWithValue| match v {
+            SchemaTypeWithValue::Int32(i) => Some(i64::from(*i)),
+            SchemaTypeWithValue::Int64(i) => Some(*i),
+            _ => None,
+        }),
+        SchemaType::Binary => build!(BinaryBuilder::new(), |v: &SchemaTypeWithValue| match v {
+            SchemaTypeWithValue::Binary(b) => Some(b.clone()),
+            SchemaTypeWithValue::String(s) => Some(s.as_bytes().to_vec()),
+            _ => None,
+        }),
+        SchemaType::Boolean => build!(BooleanBuilder::new(), |v: &SchemaTypeWithValue| match v {
+            SchemaTypeWithValue::Boolean(b) => Some(*b),
+            _ => None,
+        }),
+        SchemaType::Timestamp(precision) | SchemaType::Timestamptz(precision) => {
+            let timezone = match type_ {
+                SchemaType::Timestamptz(_) => Some(UTC),
+                _ => None,
+            };
+            // naive values have no time zone for timestamps in UTC.
+            let convert = |v: &SchemaTypeWithValue| match (timezone, v) {
+                (Some(_), SchemaTypeWithValue::Timestamptz(t)) => timestamp_in(*t, precision),
+                (Some(_), _) => None,
+                (None, v) => timestamp_of(v).and_then(|d| timestamp_in(d, precision)),
+            };
+            match precision {
+                TimePrecision::Micro => build!(
+                    TimestampMicrosecondBuilder::new().with_timezone_opt(timezone),
+                    convert
+                ),
+                TimePrecision::Nano => build!(
+                    TimestampNanosecondBuilder::new().with_timezone_opt(timezone),
+                    convert
+                ),
+            }
+        }
+        SchemaType::Date => build!(Date32Builder::new(), |v: &SchemaTypeWithValue| {
+            timestamp_of(v)
+                .and_then(|d| i32::try_from((d.date_naive() - unix_epoch_date()).num_days()).ok())
+        }),
+        SchemaType::Time => build!(
+            Time64MicrosecondBuilder::new(),
+            |v: &SchemaTypeWithValue| {
+                match v {
+                    SchemaTypeWithValue::Time(t) => Some(
+                        i64::from(t.num_seconds_from_midnight()) * 1_000_000
+                            + i64::from(t.nanosecond() / 1000),
+                    ),
+                    _ => None,
+                }
+            }
+        ),
+        SchemaType::Interval => build!(
+            IntervalMonthDayNanoBuilder::new(),
+            |v: &SchemaTypeWithValue| match v {
+                SchemaTypeWithValue::Interval(i) => i
+                    .microseconds
+                    .checked_mul(1000)
+                    .map(|nanoseconds| IntervalMonthDayNano::new(i.months, i.days, nanoseconds)),
+                _ => None,
+            }
+        ),
+        SchemaType::Double => build!(Float64Builder::new(), |v: &SchemaTypeWithValue| match v {
+            SchemaTypeWithValue::Double(f) => Some(*f),
+            SchemaTypeWithValue::Float(f) => Some(f64::from(*f)),
+            SchemaTypeWithValue::Int32(i) => Some(f64::from(*i)),
+            // numbers merged from json may mix integers and floats.
+            SchemaTypeWithValue::Int64(i) => Some(*i as f64),
+            SchemaTypeWithValue::Decimal(d) => d.to_f64(),
+            _ => None,
+        }),
+        SchemaType::Float => build!(Float32Builder::new(), |v: &SchemaTypeWithValue| match v {
+            SchemaTypeWithValue::Float(f) => Some(*f),
+            _ => None,
+        }),
+        SchemaType::Decimal(precision, scale) => {
+            let (precision, scale) = arrow_decimal(*precision, *scale);
+            build!(
+                Decimal128Builder::new()
+                    .with_precision_and_scale(u8::try_from(precision)?, i8::try_from(scale)?)?,
+                |v: &SchemaTypeWithValue| decimal128_of(v, precision, scale)
+            )
+        }
+        SchemaType::Uuid => {
+            let mut builder = FixedSizeBinaryBuilder::new(UUID_WIDTH);
+            for value in values {
+                match value {
+                    SchemaTypeWithValue::None => builder.append_null(),
+                    value => builder.append_value(
+                        value
+                            .as_uuid()
+                            .ok_or_else(|| cast_error(value, type_))?
+                            .as_bytes(),
+                    )?,
+                }
+            }
+            Arc::new(builder.finish()) as ArrayRef
+        }
+        SchemaType::List(inner) => {
+            let mut lengths: Vec<usize> = Vec::with_capacity(values.len());
+            let mut valid: Vec<bool> = Vec::with_capacity(values.len());
+            let mut items: Vec<&SchemaTypeWithValue> = Vec::new();
+            for value in values.iter().copied() {
+                match value {
+                    SchemaTypeWithValue::List(list) => {
+                        lengths.push(list.len());
+                        valid.push(true);
+                        items.extend(list);
+                    }
+                    SchemaTypeWithValue::None => {
+                        lengths.push(0);
+                        valid.push(false);
+                    }
+                    value => return Err(cast_error(value, type_)),
+                }
+            }
+            Arc::new(ListArray::try_new(
+                Arc::new(field_to_arrow(&list_item(inner))?),
+                OffsetBuffer::from_lengths(lengths),
+                build_array(&items, inner)?,
+                Some(NullBuffer::from(valid)),
+            )?) as ArrayRef
+        }
+        SchemaType::Struct(fields) => {
+            let mut valid: Vec<bool> = Vec::with_capacity(values.len());
+            for value in values.iter().copied() {
+                match value {
+                    SchemaTypeWithValue::Struct(_) => valid.push(true),
+                    SchemaTypeWithValue::None => valid.push(false),
+                    value => return Err(cast_error(value, type_)),
+                }
+            }
+            let children = fields
+                .iter()
+                .map(|field| {
+                    let field_values = values
+                        .iter()
+                        .map(|value| match value {
+                            SchemaTypeWithValue::Struct(columns) => columns
+                                .iter()
+                                .find(|column| column.name == field.name)
+                                .map_or(&SchemaTypeWithValue::None, |column| &column.value),
+                            _ => &SchemaTypeWithValue::None,
+                        })
+                        .collect::<Vec<_>>();
+                    build_array(&field_values, &field.type_)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Arc::new(StructArray::try_new_with_length(
+                fields
+                    .iter()
+                    .map(field_to_arrow)
+                    .collect::<Result<Vec<_>>>()?
+                    .into(),
+                children,
+                Some(NullBuffer::from(valid)),
+                values.len(),
+            )?) as ArrayRef
+        }
+        SchemaType::None => {
+            if let Some(value) = values
+                .iter()
+                .find(|v| !matches!(v, SchemaTypeWithValue::None))
+            {
+                return Err(cast_error(value, type_));
+            }
+            Arc::new(NullArray::new(values.len())) as ArrayRef
+        }
+    })
+}
+
+/// convert rows to a record batch of `arrow_schema`, which must be converted from `schema`.
+/// Values are cast to the types of their fields by `policy`, json fields take any value.
+pub fn rows_to_batch(
+    rows: &[Row],
+    schema: &Schema,
+    arrow_schema: SchemaRef,
+    policy: CoercionPolicy,
+) -> Result<RecordBatch> {
+    let columns = schema
+        .0
+        .iter()
+        .map(|field| {
+            let values = rows
+                .iter()
+                .map(|row| {
+                    let value = row.get(&field.name).unwrap_or(&SchemaTypeWithValue::None);
+                    match field.type_ {
+                        SchemaType::Json => Ok(value.clone()),
+                        _ => value
+                            .coerce(&field.type_, policy)
+                            .map(|(value, _)| value)
+                            .ok_or_else(|| cast_error(value, &field.type_)),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+            build_array(&values.iter().collect::<Vec<_>>(), &field.type_)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(arrow_schema, columns)?)
+}
+
+fn downcast<T: 'static>(array: &ArrayRef) -> &T {
+    array.as_any().downcast_ref::<T>().unwrap()
+}
+
+fn datetime_of(value: i64, unit: &TimeUnit) -> Result<DateTime<Utc>> {
+    let datetime = match unit {
+        TimeUnit::Second => DateTime::from_timestamp(value, 0),
+        TimeUnit::Millisecond => DateTime::from_timestamp_millis(value),
+        TimeUnit::Microsecond => DateTime::from_timestamp_micros(value),
+        TimeUnit::Nanosecond => Some(DateTime::from_timestamp_nanos(value)),
+    };
+    datetime.ok_or(anyhow!("timestamp {value} out of range"))
+}
+
+fn time_of(value: i64, unit: &TimeUnit) -> Result<NaiveTime> {
+    let nanoseconds = match unit {
+        TimeUnit::Second => value.checked_mul(1_000_000_000),
+        TimeUnit::Millisecond => value.checked_mul(1_000_000),
+        TimeUnit::Microsecond => value.checked_mul(1000),
+        TimeUnit::Nanosecond => Some(value),
+    };
+    nanoseconds
+        .and_then(|nanoseconds| {
+            NaiveTime::from_num_seconds_from_midnight_opt(
+                u32::try_from(nanoseconds / 1_000_000_000).ok()?,
+                u32::try_from(nanoseconds % 1_000_000_000).ok()?,
+            )
+        })
+        .ok_or(anyhow!("time {value} out of range"))
+}
+
+// value at `idx` of array, the array type must be convertable by `arrow_type_to_schema`.
+fn array_value(array: &ArrayRef, idx: usize) -> Result<SchemaTypeWithValue> {
+    if array.is_null(idx) {
+        return Ok(SchemaTypeWithValue::None);
+    }
+    Ok(match array.data_type() {
+        DataType::Utf8 => {
+            SchemaTypeWithValue::String(downcast::<StringArray>(array).value(idx).into())
+        }
+        DataType::LargeUtf8 => {
+            SchemaTypeWithValue::String(downcast::<LargeStringArray>(array).value(idx).into())
+        }
+        DataType::Int8 => {
+            SchemaTypeWithValue::Int32(downcast::<Int8Array>(array).value(idx).into())
+        }
+        DataType::Int16 => {
+            SchemaTypeWithValue::Int32(downcast::<Int16Array>(array).value(idx).into())
+        }
+        DataType::Int32 => SchemaTypeWithValue::Int32(downcast::<Int32Array>(array).value(idx)),
+        DataType::UInt8 => {
+            SchemaTypeWithValue::Int32(downcast::<UInt8Array>(array).value(idx).into())
+        }
+        DataType::UInt16 => {
+            SchemaTypeWithValue::Int32(downcast::<UInt16Array>(array).value(idx).into())
+        }
+        DataType::Int64 => SchemaTypeWithValue::Int64(downcast::<Int64Array>(array).value(idx)),
+        DataType::UInt32 => {
+            SchemaTypeWithValue::Int64(downcast::<UInt32Array>(array).value(idx).into())
+        }
+        DataType::Binary => {
+            SchemaTypeWithValue::Binary(downcast::<BinaryArray>(array).value(idx).to_vec())
+        }
+        DataType::LargeBinary => {
+            SchemaTypeWithValue::Binary(downcast::<LargeBinaryArray>(array).value(idx).to_vec())
+        }
+        DataType::FixedSizeBinary(UUID_WIDTH) => SchemaTypeWithValue::Uuid(Uuid::from_slice(
+            downcast::<FixedSizeBinaryArray>(array).value(idx),
+        )?),
+        DataType::FixedSizeBinary(_) => {
+            SchemaTypeWithValue::Binary(downcast::<FixedSizeBinaryArray>(array).value(idx).to_vec())
+        }
+        DataType::Boolean => {
+            SchemaTypeWithValue::Boolean(downcast::<BooleanArray>(array).value(idx))
+        }
+        DataType::Timestamp(unit, timezone) => {
+            let value = match unit {
+                TimeUnit::Second => downcast::<TimestampSecondArray>(array).value(idx),
+                TimeUnit::Millisecond => downcast::<TimestampMillisecondArray>(array).value(idx),
+                TimeUnit::Microsecond => downcast::<TimestampMicrosecondArray>(array).value(idx),
+                TimeUnit::Nanosecond => downcast::<TimestampNanosecondArray>(array).value(idx),
+            };
+            let datetime = datetime_of(value, unit)?;
+            match timezone {
+                Some(_) => SchemaTypeWithValue::Timestamptz(datetime),
+                None => SchemaTypeWithValue::Timestamp(datetime.naive_utc()),
+            }
+        }
+        DataType::Date32 => {
+            let days = downcast::<Date32Array>(array).value(idx);
+            SchemaTypeWithValue::Date(unix_epoch_date() + chrono::Duration::days(i64::from(days)))
+        }
+        DataType::Time32(unit) => {
+            let value = match unit {
+                TimeUnit::Second => downcast::<Time32SecondArray>(array).value(idx),
+                _ => downcast::<Time32MillisecondArray>(array).value(idx),
+            };
+            SchemaTypeWithValue::Time(time_of(i64::from(value), unit)?)
+        }
+        DataType::Time64(unit) => {
+            let value = match unit {
+                TimeUnit::Nanosecond => downcast::<Time64NanosecondArray>(array).value(idx),
+                _ => downcast::<Time64MicrosecondArray>(array).value(idx),
+            };
+            SchemaTypeWithValue::Time(time_of(value, unit)?)
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            let interval = downcast::<IntervalMonthDayNanoArray>(array).value(idx);
+            SchemaTypeWithValue::Interval(Interval {
+                months: interval.months,
+                days: interval.days,
+                microseconds: interval.nanoseconds / 1000,
+            })
+        }
+        DataType::Float64 => {
+            SchemaTypeWithValue::Double(downcast::<Float64Array>(array).value(idx))
+        }
+        DataType::Float32 => SchemaTypeWithValue::Float(downcast::<Float32Array>(array).value(idx)),
+        DataType::Decimal128(_, scale) => SchemaTypeWithValue::Decimal(BigDecimal::new(
+            BigInt::from(downcast::<Decimal128Array>(array).value(idx)),
+            i64::from(*scale),
+        )),
+        DataType::List(item) => {
+            let list = downcast::<ListArray>(array).value(idx);
+            SchemaTypeWithValue::List(
+                (0..list.len())
+                    .map(|i| field_value(item, &list, i))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        }
+        DataType::LargeList(item) => {
+            let list = downcast::<LargeListArray>(array).value(idx);
+            SchemaTypeWithValue::List(
+                (0..list.len())
+                    .map(|i| field_value(item, &list, i))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        }
+        DataType::Struct(fields) => SchemaTypeWithValue::Struct(
+            fields
+                .iter()
+                .zip(downcast::<StructArray>(array).columns())
+                .map(|(field, child)| {
+                    Ok(Column {
+                        name: field.name().clone(),
+                        value: field_value(field, child, idx)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        DataType::Null => SchemaTypeWithValue::None,
+        unk => {
+            return Err(anyhow!(
+                "cannot convert arrow type {unk}, may not supported yet."
+            ))
+        }
+    })
+}
+
+// value at `idx` of the array of `field`, json strings are parsed.
+fn field_value(field: &Field, array: &ArrayRef, idx: usize) -> Result<SchemaTypeWithValue> {
+    Ok(match array_value(array, idx)? {
+        SchemaTypeWithValue::String(s) if is_json_field(field) => {
+            SchemaTypeWithValue::Json(serde_json::from_str(&s)?)
+        }
+        value => value,
+    })
+}
+
+pub fn batch_to_rows(batch: &RecordBatch) -> Result<Vec<Row>> {
+    let schema = batch.schema();
+    (0..batch.num_rows())
+        .map(|idx| {
+            Ok(Row(schema
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .map(|(field, array)| {
+                    Ok(Column {
+                        name: field.name().clone(),
+                        value: field_value(field, array, idx)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?))
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// schema of most types and rows in it, the second row is null except `id`.
+    pub(in crate::data_storages) fn typed_rows() -> (Schema, Vec<Row>) {
+        let field = |name: &str, type_: SchemaType| SchemaField {
+            name: name.to_string(),
+            type_,
+            extra: HashMap::new(),
+        };
+        let schema = Schema(vec![
+            field("id", SchemaType::Int64),
+            field("name", SchemaType::String),
+            field("data", SchemaType::Binary),
+            field("flag", SchemaType::Boolean),
+            field("at", SchemaType::Timestamptz(TimePrecision::Micro)),
+            field("day", SchemaType::Date),
+            field("amount", SchemaType::Decimal(10, 2)),
+            field("uid", SchemaType::Uuid),
+            field("span", SchemaType::Interval),
+            field("tags", SchemaType::List(Box::new(SchemaType::Int64))),
+        ]);
+        let values = vec![
+            SchemaTypeWithValue::Int64(1),
+            SchemaTypeWithValue::String("a,\"b\"".to_string()),
+            SchemaTypeWithValue::Binary(vec![0, 255]),
+            SchemaTypeWithValue::Boolean(true),
+            SchemaTypeWithValue::Timestamptz(
+                DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+            ),
+            SchemaTypeWithValue::Date(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()),
+            SchemaTypeWithValue::Decimal("-12.50".parse().unwrap()),
+            SchemaTypeWithValue::Uuid(Uuid::from_u128(0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8)),
+            SchemaTypeWithValue::Interval(Interval {
+                months: 14,
+                days: -3,
+                microseconds: 14_706_500_000,
+            }),
+            SchemaTypeWithValue::List(vec![
+                SchemaTypeWithValue::Int64(1),
+                SchemaTypeWithValue::None,
+            ]),
+        ];
+        let row = |values: Vec<SchemaTypeWithValue>| {
+            Row(schema
+                .0
+                .iter()
+                .zip(values)
+                .map(|(field, value)| Column {
+                    name: field.name.clone(),
+                    value,
+                })
+                .collect())
+        };
+        let mut nulls = vec![SchemaTypeWithValue::None; values.len()];
+        nulls[0] = SchemaTypeWithValue::Int64(2);
+        let rows = vec![row(values), row(nulls)];
+        (schema, rows)
+    }
+
+    #[test]
+    fn rows_to_batch_and_back() {
+        let (schema, rows) = typed_rows();
+        let arrow_schema = Arc::new(schema_to_arrow(&schema).unwrap());
+        let batch =
+            rows_to_batch(&rows, &schema, arrow_schema.clone(), CoercionPolicy::Strict).unwrap();
+        assert_eq!(batch_to_rows(&batch).unwrap(), rows);
+        let types = |schema: &Schema| schema.0.iter().map(|f| f.type_.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            types(&arrow_to_schema(&arrow_schema).unwrap()),
+            types(&schema)
+        );
+    }
+
+    #[test]
+    fn rows_to_batch_casts_by_policy() {
+        let field = |name: &str, type_: SchemaType| SchemaField {
+            name: name.to_string(),
+            type_,
+            extra: HashMap::new(),
+        };
+        let column = |name: &str, value: SchemaTypeWithValue| Column {
+            name: name.to_string(),
+            value,
+        };
+        let at = DateTime::from_timestamp(0, 1).unwrap().naive_utc();
+        let schema = |precision| {
+            Schema(vec![
+                field("i", SchemaType::Int32),
+                field("f", SchemaType::Float),
+                field("at", SchemaType::Timestamp(precision)),
+                field("doc", SchemaType::Json),
+            ])
+        };
+        let rows = vec![Row(vec![
+            column("i", SchemaTypeWithValue::Int64(7)),
+            column("f", SchemaTypeWithValue::Double(0.5)),
+            column("at", SchemaTypeWithValue::Timestamp(at)),
+            column("doc", SchemaTypeWithValue::String("a\"b".to_string())),
+        ])];
+        let batch = |precision, policy| {
+            let schema = schema(precision);
+            let arrow_schema = Arc::new(schema_to_arrow(&schema).unwrap());
+            rows_to_batch(&rows, &schema, arrow_schema, policy)
+        };
+
+        let nano = batch(TimePrecision::Nano, CoercionPolicy::Safe).unwrap();
+        assert_eq!(
+            batch_to_rows(&nano).unwrap(),
+            vec![Row(vec![
+                column("i", SchemaTypeWithValue::Int32(7)),
+                column("f", SchemaTypeWithValue::Float(0.5)),
+                column("at", SchemaTypeWithValue::Timestamp(at)),
+                column("doc", SchemaTypeWithValue::Json(serde_json::json!("a\"b"))),
+            ])]
+        );
+        assert!(batch(TimePrecision::Nano, CoercionPolicy::Strict).is_err());
+        // nanoseconds are not truncated unless lossy.
+        assert!(batch(TimePrecision::Micro, CoercionPolicy::Safe).is_err());
+        let micro = batch(TimePrecision::Micro, CoercionPolicy::Lossy).unwrap();
+        assert_eq!(
+            batch_to_rows(&micro).unwrap()[0].get("at"),
+            Some(&SchemaTypeWithValue::Timestamp(
+                DateTime::UNIX_EPOCH.naive_utc()
+            ))
+        );
+    }
+}
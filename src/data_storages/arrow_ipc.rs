@@ -0,0 +1,278 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use arrow::{
+    datatypes::SchemaRef,
+    ipc::{
+        reader::{FileReader, StreamReader},
+        writer::{FileWriter, IpcWriteOptions, StreamWriter},
+        CompressionType,
+    },
+    record_batch::RecordBatchReader,
+};
+use async_trait::async_trait;
+
+use super::{
+    arrow_convert::{arrow_to_schema, batch_to_rows, rows_to_batch, schema_to_arrow},
+    data_storages::{CoercionPolicy, ReadResult, Row, Schema, SchemaTypeWithValue},
+    error::ParameterError,
+    utils::{extract_file_uri_path, option_parse},
+    DataStorage,
+};
+
+// magic bytes at the beginning of the ipc file format, streams have no magic.
+const FILE_MAGIC: &[u8; 6] = b"ARROW1";
+
+type BatchReader = Box<dyn RecordBatchReader + Send>;
+
+enum IpcWriter {
+    File(FileWriter<BufWriter<fs::File>>),
+    Stream(StreamWriter<BufWriter<fs::File>>),
+}
+
+pub struct ArrowDataStorage {
+    file: String,
+    // reader kept between `chunk_read` calls and the index of next batch it reads.
+    chunk_reader: Option<(BatchReader, i64)>,
+    // writer and the schema of rows it writes, created on first write.
+    writer: Option<(IpcWriter, Schema, SchemaRef)>,
+}
+
+impl ArrowDataStorage {
+    pub fn new(uri: &str) -> Self {
+        ArrowDataStorage {
+            file: extract_file_uri_path(uri, "file+arrow"),
+            chunk_reader: None,
+            writer: None,
+        }
+    }
+
+    /// reader of either ipc file(feather v2) or ipc stream, detected by the magic bytes.
+    fn reader(&self) -> Result<BatchReader> {
+        let mut file = fs::File::open(&self.file)?;
+        let mut magic = [0u8; FILE_MAGIC.len()];
+        let is_file_format = file.read_exact(&mut magic).is_ok() && &magic == FILE_MAGIC;
+        file.rewind()?;
+        let reader = BufReader::new(file);
+        Ok(if is_file_format {
+            Box::new(FileReader::try_new(reader, None)?) as BatchReader
+        } else {
+            Box::new(StreamReader::try_new(reader, None)?) as BatchReader
+        })
+    }
+}
+
+fn write_options(options: &HashMap<&str, &str>) -> Result<IpcWriteOptions> {
+    let compression = match options
+        .get("compression")
+        .map(|compression| compression.to_lowercase())
+        .as_deref()
+    {
+        None | Some("uncompressed") => None,
+        Some("lz4") => Some(CompressionType::LZ4_FRAME),
+        Some("zstd") => Some(CompressionType::ZSTD),
+        Some(unk) => {
+            return Err(ParameterError::new(
+                format!("`compression` must be `uncompressed`, `lz4` or `zstd`, got `{unk}`")
+                    .as_str(),
+            )
+            .into())
+        }
+    };
+    Ok(IpcWriteOptions::default().try_with_compression(compression)?)
+}
+
+#[async_trait]
+impl DataStorage for ArrowDataStorage {
+    /// schema embedded in the ipc file or stream.
+    async fn read_schema(&mut self, _: &HashMap<&str, &str>) -> Result<Schema> {
+        arrow_to_schema(&self.reader()?.schema())
+    }
+
+    async fn read(&mut self, _: &HashMap<&str, &str>) -> Result<ReadResult> {
+        let reader = self.reader()?;
+        let schema = arrow_to_schema(&reader.schema())?;
+        let mut data: Vec<Row> = Vec::new();
+        for batch in reader {
+            data.extend(batch_to_rows(&batch?)?);
+        }
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor: None,
+        })
+    }
+
+    /// each chunk is one record batch, `limit` is ignored. The cursor is the index of next batch.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        _: u32,
+        _: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let index = match cursor {
+            None => 0,
+            Some(SchemaTypeWithValue::Int64(index)) => index,
+            Some(unk) => {
+                return Err(ParameterError::new(
+                    format!("invalid cursor {unk:?} for arrow, must be a batch index").as_str(),
+                )
+                .into())
+            }
+        };
+        // reuse the reader if it stops at the cursor, otherwise reopen file and skip to it.
+        let (mut reader, mut next) = match self.chunk_reader.take() {
+            Some((reader, next)) if next == index => (reader, next),
+            _ => (self.reader()?, 0),
+        };
+        let schema = arrow_to_schema(&reader.schema())?;
+        // skip empty batches, empty data means the end of file.
+        let mut data: Vec<Row> = Vec::new();
+        while data.is_empty() {
+            match reader.next() {
+                Some(batch) => {
+                    let batch = batch?;
+                    next += 1;
+                    if next > index {
+                        data = batch_to_rows(&batch)?;
+                    }
+                }
+                None => break,
+            }
+        }
+        let cursor = if data.is_empty() {
+            None
+        } else {
+            self.chunk_reader = Some((reader, next));
+            Some(SchemaTypeWithValue::Int64(next))
+        };
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor,
+        })
+    }
+
+    /// each write is one record batch, `format=stream` writes ipc stream instead of ipc file.
+    /// The file is completed on `close`.
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        if self.writer.is_none() {
+            let schema = match (schema, data.first()) {
+                (Some(schema), _) => schema,
+                (None, Some(row)) => row.schema(),
+                (None, None) => return Ok(()),
+            };
+            let file = BufWriter::new(fs::File::create(&self.file)?);
+            let arrow_schema = Arc::new(schema_to_arrow(&schema)?);
+            let write_options = write_options(options)?;
+            let writer = match options
+                .get("format")
+                .map(|format| format.to_lowercase())
+                .as_deref()
+            {
+                None | Some("file") => IpcWriter::File(FileWriter::try_new_with_options(
+                    file,
+                    &arrow_schema,
+                    write_options,
+                )?),
+                Some("stream") => IpcWriter::Stream(StreamWriter::try_new_with_options(
+                    file,
+                    &arrow_schema,
+                    write_options,
+                )?),
+                Some(unk) => {
+                    return Err(ParameterError::new(
+                        format!("`format` must be `file` or `stream`, got `{unk}`").as_str(),
+                    )
+                    .into())
+                }
+            };
+            self.writer = Some((writer, schema, arrow_schema));
+        }
+        let (writer, schema, arrow_schema) = self.writer.as_mut().unwrap();
+        if !data.is_empty() {
+            let policy = option_parse(options, "coercion", CoercionPolicy::default())?;
+            let batch = rows_to_batch(&data, schema, arrow_schema.clone(), policy)?;
+            match writer {
+                IpcWriter::File(writer) => writer.write(&batch)?,
+                IpcWriter::Stream(writer) => writer.write(&batch)?,
+            }
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        // dropping the buffered file would swallow errors of its last write.
+        let mut file = match self.writer.take() {
+            Some((IpcWriter::File(writer), _, _)) => writer.into_inner()?,
+            Some((IpcWriter::Stream(writer), _, _)) => writer.into_inner()?,
+            None => return Ok(()),
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::arrow_convert::tests::typed_rows;
+
+    #[tokio::test]
+    async fn write_and_read_batches() {
+        let (schema, rows) = typed_rows();
+        for (format, compression) in [
+            ("file", "lz4"),
+            ("stream", "zstd"),
+            ("file", "uncompressed"),
+        ] {
+            let path = std::env::temp_dir()
+                .join(format!(
+                    "datawhirr-arrow-{format}-{compression}-{}",
+                    std::process::id()
+                ))
+                .to_string_lossy()
+                .to_string();
+            let uri = format!("file+arrow://{path}");
+            let options = HashMap::from([("format", format), ("compression", compression)]);
+            let mut storage = ArrowDataStorage::new(uri.as_str());
+            // one batch per write.
+            for row in &rows {
+                storage
+                    .write(vec![row.clone()], Some(schema.clone()), &options)
+                    .await
+                    .unwrap();
+            }
+            storage.close().await.unwrap();
+
+            let mut storage = ArrowDataStorage::new(uri.as_str());
+            let all = storage.read(&options).await.unwrap();
+            let mut chunks = vec![];
+            let mut cursor = None;
+            loop {
+                let chunk = storage.chunk_read(cursor, 10, &options).await.unwrap();
+                if chunk.data.is_empty() {
+                    break;
+                }
+                cursor = chunk.cursor;
+                chunks.push(chunk.data);
+            }
+            fs::remove_file(&path).unwrap();
+            assert_eq!(all.data, rows, "{format} in {compression}");
+            let types =
+                |schema: &Schema| schema.0.iter().map(|f| f.type_.clone()).collect::<Vec<_>>();
+            assert_eq!(types(&all.schema), types(&schema));
+            assert_eq!(chunks, vec![rows[..1].to_vec(), rows[1..].to_vec()]);
+        }
+    }
+}
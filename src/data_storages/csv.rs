@@ -1,54 +1,446 @@
-use core::panic;
-
-use super::DataStorage;
-use csv;
-use std::path;
+use super::{
+    data_storages::{
+        BinaryEncoding, Column, ReadResult, Row, Schema, SchemaField, SchemaType,
+        SchemaTypeWithValue,
+    },
+    error::ParameterError,
+    infer::{apply_schema, infer_schema, InferOptions},
+    utils::{extract_file_uri_path, option_bool},
+    DataStorage,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Read, Write},
+};
 
 #[derive(Debug)]
 pub struct CSVDataStorage {
     file: String,
-}
-
-fn extract_csv_uri_filepath(uri: &str) -> String {
-    if uri.starts_with("file+csv:///") {
-        uri.replace("file+csv://", "")
-            .replace("$PWD", path::absolute(".").unwrap().to_str().unwrap())
-    } else {
-        panic!("not support auth/host uri, only `file+csv:///absoult/path` or `file+csv:///$PWD/relative/path` has been supported")
-    }
+    // reader kept between `chunk_read` calls and the number of records it has consumed.
+    chunk_reader: Option<(csv::Reader<fs::File>, i64)>,
+    writer: Option<csv::Writer<fs::File>>,
+    // schema inferred on first use if `infer_schema=true`, shared by later reads.
+    inferred: Option<Schema>,
 }
 
 impl CSVDataStorage {
     pub fn new(uri: &str) -> Self {
         CSVDataStorage {
-            file: extract_csv_uri_filepath(uri),
+            file: extract_file_uri_path(uri, "file+csv"),
+            chunk_reader: None,
+            writer: None,
+            inferred: None,
+        }
+    }
+
+    // schema inferred from the first records, None if inference is not enabled.
+    fn inferred_schema(
+        &mut self,
+        csv_options: &CSVOptions,
+        options: &HashMap<&str, &str>,
+    ) -> Result<Option<Schema>> {
+        let infer_options = InferOptions::from_options(options)?;
+        if !infer_options.enabled {
+            return Ok(None);
+        }
+        if self.inferred.is_none() {
+            let mut reader = csv_options.reader(fs::File::open(&self.file)?);
+            let names = column_names(&mut reader, csv_options.header)?;
+            let sample = reader
+                .records()
+                .take(infer_options.sample_size)
+                .map(|record| Ok(csv_options.record_to_row(&record?, &names)))
+                .collect::<Result<Vec<_>>>()?;
+            self.inferred =
+                Some(infer_schema(&sample, true).unwrap_or_else(|| names_to_schema(&names)));
+        }
+        Ok(self.inferred.clone())
+    }
+}
+
+pub(super) struct CSVOptions {
+    pub(super) header: bool,
+    delimiter: u8,
+    quote: u8,
+    escape: Option<u8>,
+    terminator: csv::Terminator,
+    // text which represents null value, both for read and write. There is none by default, so
+    // empty fields are read as empty strings and nulls are written as empty fields.
+    null_value: Option<String>,
+    // append to existing file instead of truncating it on first write.
+    append: bool,
+    binary_encoding: BinaryEncoding,
+}
+
+fn option_byte(options: &HashMap<&str, &str>, key: &str) -> Result<Option<u8>> {
+    match options.get(key) {
+        None => Ok(None),
+        Some(value) if value.len() == 1 => Ok(Some(value.as_bytes()[0])),
+        Some(value) if *value == "\\t" => Ok(Some(b'\t')),
+        Some(value) => Err(ParameterError::new(
+            format!("`{key}` must be a single byte character, got `{value}`").as_str(),
+        )
+        .into()),
+    }
+}
+
+pub(super) fn parse_csv_options(options: &HashMap<&str, &str>) -> Result<CSVOptions> {
+    let terminator = match options
+        .get("terminator")
+        .map(|t| t.to_lowercase())
+        .as_deref()
+    {
+        None | Some("crlf") => csv::Terminator::CRLF,
+        Some("lf") => csv::Terminator::Any(b'\n'),
+        Some("cr") => csv::Terminator::Any(b'\r'),
+        Some(_) => csv::Terminator::Any(option_byte(options, "terminator")?.unwrap()),
+    };
+    Ok(CSVOptions {
+        header: option_bool(options, "header", true)?,
+        delimiter: option_byte(options, "delimiter")?.unwrap_or(b','),
+        quote: option_byte(options, "quote")?.unwrap_or(b'"'),
+        escape: option_byte(options, "escape")?,
+        terminator,
+        null_value: options.get("null_value").map(|null| null.to_string()),
+        append: option_bool(options, "append", false)?,
+        binary_encoding: BinaryEncoding::from_options(options)?,
+    })
+}
+
+impl CSVOptions {
+    pub(super) fn reader<R: Read>(&self, source: R) -> csv::Reader<R> {
+        csv::ReaderBuilder::new()
+            .has_headers(self.header)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .terminator(self.terminator)
+            .from_reader(source)
+    }
+
+    pub(super) fn writer<W: Write>(&self, sink: W) -> csv::Writer<W> {
+        let mut builder = csv::WriterBuilder::new();
+        builder
+            .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .terminator(match self.terminator {
+                // `CRLF` of reader accepts any line ending, write unix style.
+                csv::Terminator::CRLF => csv::Terminator::Any(b'\n'),
+                terminator => terminator,
+            });
+        if let Some(escape) = self.escape {
+            builder.double_quote(false).escape(escape);
+        }
+        builder.from_writer(sink)
+    }
+
+    pub(super) fn record_to_row(&self, record: &csv::StringRecord, names: &[String]) -> Row {
+        Row(names
+            .iter()
+            .zip(record.iter())
+            .map(|(name, field)| Column {
+                name: name.clone(),
+                value: if self.null_value.as_deref() == Some(field) {
+                    SchemaTypeWithValue::None
+                } else {
+                    SchemaTypeWithValue::String(field.to_string())
+                },
+            })
+            .collect::<Vec<_>>())
+    }
+
+    pub(super) fn write_header<W: Write>(
+        &self,
+        writer: &mut csv::Writer<W>,
+        schema: &Schema,
+    ) -> Result<()> {
+        Ok(writer.write_record(schema.0.iter().map(|field| field.name.as_str()))?)
+    }
+
+    // write rows in the order of schema and flush them.
+    pub(super) fn write_rows<W: Write>(
+        &self,
+        writer: &mut csv::Writer<W>,
+        data: &[Row],
+        schema: &Schema,
+    ) -> Result<()> {
+        for row in data {
+            writer.write_record(schema.0.iter().map(|field| {
+                row.get(&field.name)
+                    .and_then(|value| value.encode_text(self.binary_encoding))
+                    .or(self.null_value.clone())
+                    .unwrap_or_default()
+            }))?;
         }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// column names from header, or `column_{idx}` if the file has no header.
+pub(super) fn column_names<R: Read>(
+    reader: &mut csv::Reader<R>,
+    header: bool,
+) -> Result<Vec<String>> {
+    if header {
+        Ok(reader.headers()?.iter().map(String::from).collect())
+    } else {
+        let len = reader.headers()?.len();
+        Ok((0..len).map(|idx| format!("column_{idx}")).collect())
     }
 }
 
+pub(super) fn names_to_schema(names: &[String]) -> Schema {
+    Schema(
+        names
+            .iter()
+            .map(|name| SchemaField {
+                name: name.clone(),
+                type_: SchemaType::String,
+                extra: HashMap::from([("nullable".to_string(), "true".to_string())]),
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[async_trait]
 impl DataStorage for CSVDataStorage {
-    async fn read(
-        &mut self,
-        options: &std::collections::HashMap<&str, &str>,
-    ) -> anyhow::Result<super::data_storages::ReadResult> {
+    async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult> {
+        let csv_options = parse_csv_options(options)?;
+        let mut reader = csv_options.reader(fs::File::open(&self.file)?);
+        let names = column_names(&mut reader, csv_options.header)?;
+        let data = reader
+            .records()
+            .map(|record| Ok(csv_options.record_to_row(&record?, &names)))
+            .collect::<Result<Vec<_>>>()?;
+        let (data, schema) = match self.inferred_schema(&csv_options, options)? {
+            Some(schema) => (apply_schema(data, &schema, 0)?, schema),
+            None => (data, names_to_schema(&names)),
+        };
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor: None,
+        })
     }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    /// write rows in the order of schema, the file will be truncated and the header will be
+    /// written on the first write unless `append=true`.
     async fn write(
         &mut self,
-        data: Vec<super::data_storages::Row>,
-        schema: Option<super::data_storages::Schema>,
-        options: &std::collections::HashMap<&str, &str>,
-    ) -> anyhow::Result<()> {
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        let csv_options = parse_csv_options(options)?;
+        let schema = match (schema, data.first()) {
+            (Some(schema), _) => schema,
+            (None, Some(row)) => row.schema(),
+            (None, None) => return Ok(()),
+        };
+        if self.writer.is_none() {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(csv_options.append)
+                .truncate(!csv_options.append)
+                .open(&self.file)?;
+            let is_empty = file.metadata()?.len() == 0;
+            let mut writer = csv_options.writer(file);
+            if csv_options.header && is_empty {
+                csv_options.write_header(&mut writer, &schema)?;
+            }
+            self.writer = Some(writer);
+        }
+        csv_options.write_rows(self.writer.as_mut().unwrap(), &data, &schema)
     }
+
+    /// cursor is the number of records has been read.
     async fn chunk_read(
         &mut self,
-        cursor: Option<super::data_storages::SchemaTypeWithValue>,
+        cursor: Option<SchemaTypeWithValue>,
         limit: u32,
-        options: &std::collections::HashMap<&str, &str>,
-    ) -> anyhow::Result<super::data_storages::ReadResult> {
+        options: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let csv_options = parse_csv_options(options)?;
+        let offset = match cursor {
+            None => 0,
+            Some(SchemaTypeWithValue::Int64(offset)) => offset,
+            Some(unk) => {
+                return Err(ParameterError::new(
+                    format!("invalid cursor {unk:?} for csv, must be a row offset").as_str(),
+                )
+                .into())
+            }
+        };
+        // reuse the reader if it stops at the cursor, otherwise reopen file and skip to it.
+        let (mut reader, mut consumed) = match self.chunk_reader.take() {
+            Some((reader, consumed)) if consumed == offset => (reader, consumed),
+            _ => (csv_options.reader(fs::File::open(&self.file)?), 0),
+        };
+        let names = column_names(&mut reader, csv_options.header)?;
+        let mut record = csv::StringRecord::new();
+        while consumed < offset && reader.read_record(&mut record)? {
+            consumed += 1;
+        }
+        let start = usize::try_from(consumed)?;
+        let mut data: Vec<Row> = Vec::new();
+        while data.len() < limit as usize && reader.read_record(&mut record)? {
+            consumed += 1;
+            data.push(csv_options.record_to_row(&record, &names));
+        }
+        let cursor = if data.is_empty() {
+            None
+        } else {
+            self.chunk_reader = Some((reader, consumed));
+            Some(SchemaTypeWithValue::Int64(consumed))
+        };
+        let (data, schema) = match self.inferred_schema(&csv_options, options)? {
+            Some(schema) => (apply_schema(data, &schema, start)?, schema),
+            None => (data, names_to_schema(&names)),
+        };
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor,
+        })
     }
-    async fn read_schema(
-        &mut self,
-        options: &std::collections::HashMap<&str, &str>,
-    ) -> anyhow::Result<super::data_storages::Schema> {
+
+    /// names of columns typed as String, or schema inferred from the first `sample_size` records
+    /// if `infer_schema=true`.
+    async fn read_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Schema> {
+        let csv_options = parse_csv_options(options)?;
+        if let Some(schema) = self.inferred_schema(&csv_options, options)? {
+            return Ok(schema);
+        }
+        let mut reader = csv_options.reader(fs::File::open(&self.file)?);
+        Ok(names_to_schema(&column_names(
+            &mut reader,
+            csv_options.header,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, value: SchemaTypeWithValue) -> Column {
+        Column {
+            name: name.to_string(),
+            value,
+        }
+    }
+
+    #[tokio::test]
+    async fn write_and_read_back() {
+        let rows = vec![
+            Row(vec![
+                column("id", SchemaTypeWithValue::Int64(1)),
+                column("name", SchemaTypeWithValue::String(r#"a;"b""#.to_string())),
+                column("data", SchemaTypeWithValue::Binary(vec![0, 255])),
+            ]),
+            Row(vec![
+                column("id", SchemaTypeWithValue::Int64(2)),
+                column("name", SchemaTypeWithValue::None),
+                column("data", SchemaTypeWithValue::None),
+            ]),
+        ];
+        for (encoding, encoded) in [("hex", "00ff"), ("base64", "AP8=")] {
+            let path = std::env::temp_dir()
+                .join(format!("datawhirr-csv-{encoding}-{}", std::process::id()))
+                .to_string_lossy()
+                .to_string();
+            let options = HashMap::from([
+                ("delimiter", ";"),
+                ("escape", "\\"),
+                ("null_value", "NULL"),
+                ("binary_encoding", encoding),
+            ]);
+            let mut storage = CSVDataStorage::new(format!("file+csv://{path}").as_str());
+            storage.write(rows.clone(), None, &options).await.unwrap();
+            assert_eq!(
+                fs::read_to_string(&path).unwrap(),
+                format!("id;name;data\n1;\"a;\\\"b\\\"\";{encoded}\n2;NULL;NULL\n")
+            );
+            let result = CSVDataStorage::new(format!("file+csv://{path}").as_str())
+                .read(&options)
+                .await
+                .unwrap();
+            fs::remove_file(&path).unwrap();
+            let text = |s: &str| SchemaTypeWithValue::String(s.to_string());
+            assert_eq!(
+                result.data,
+                vec![
+                    Row(vec![
+                        column("id", text("1")),
+                        column("name", text(r#"a;"b""#)),
+                        column("data", text(encoded)),
+                    ]),
+                    Row(vec![
+                        column("id", text("2")),
+                        column("name", SchemaTypeWithValue::None),
+                        column("data", SchemaTypeWithValue::None),
+                    ]),
+                ]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn empty_string_is_not_null_by_default() {
+        let rows = vec![Row(vec![
+            column("a", SchemaTypeWithValue::String(String::new())),
+            column("b", SchemaTypeWithValue::None),
+        ])];
+        let empty = SchemaTypeWithValue::String(String::new());
+        // an empty string and null are told apart only by a non-empty `null_value`.
+        for (null_value, written, a, b) in [
+            (None, "a,b\n,\n", empty.clone(), empty.clone()),
+            (
+                Some(""),
+                "a,b\n,\n",
+                SchemaTypeWithValue::None,
+                SchemaTypeWithValue::None,
+            ),
+            (
+                Some("\\N"),
+                "a,b\n,\\N\n",
+                empty.clone(),
+                SchemaTypeWithValue::None,
+            ),
+        ] {
+            let path = std::env::temp_dir()
+                .join(format!("datawhirr-csv-null-{}", std::process::id()))
+                .to_string_lossy()
+                .to_string();
+            let options =
+                null_value.map_or(HashMap::new(), |null| HashMap::from([("null_value", null)]));
+            let mut storage = CSVDataStorage::new(format!("file+csv://{path}").as_str());
+            storage.write(rows.clone(), None, &options).await.unwrap();
+            assert_eq!(
+                fs::read_to_string(&path).unwrap(),
+                written,
+                "{null_value:?}"
+            );
+            let result = CSVDataStorage::new(format!("file+csv://{path}").as_str())
+                .read(&options)
+                .await
+                .unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(
+                result.data,
+                vec![Row(vec![column("a", a), column("b", b)])],
+                "{null_value:?}"
+            );
+        }
     }
 }
@@ -1,40 +1,758 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bigdecimal::{BigDecimal, Signed, ToPrimitive};
+use chrono::{
+    DateTime, DurationRound, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeDelta,
+    Timelike, Utc,
+};
+use regex::Regex;
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::{cmp::Ordering, collections::HashMap, mem, str::FromStr, sync::LazyLock};
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::{error::CastError, error::ParameterError, utils::merge_type};
+
+// derived serde impls are inherent functions, the traits are implemented below to read the
+// types of old configs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub enum SchemaType {
     String,
     Int32,
     Int64,
     Binary,
     Boolean,
-    Timestamp,
+    /// timestamp without time zone.
+    Timestamp(TimePrecision),
+    /// timestamp in UTC.
+    Timestamptz(TimePrecision),
     Date,
-    Datetime,
+    /// time of day without time zone.
+    Time,
+    Interval,
     Double,
     Float,
+    /// exact numeric with precision and scale, `Decimal(0, 0)` is unbounded, e.g. `numeric` of
+    /// postgres without type modifiers.
+    Decimal(u16, i16),
+    Uuid,
+    Json,
+    /// list of values typed as the inner type.
+    List(Box<SchemaType>),
+    /// nested fields.
+    Struct(Vec<SchemaField>),
     None,
 }
 
-#[derive(Clone, Debug)]
+// `Datetime` of configs before timestamps had precisions.
+const LEGACY_DATETIME: &str = "Datetime";
+
+impl Serialize for SchemaType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SchemaType::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaType {
+    /// derived form, or `Datetime` which is `Timestamp(Micro)`.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SchemaTypeVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for SchemaTypeVisitor {
+            type Value = SchemaType;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("schema type")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<SchemaType, E> {
+                match v {
+                    LEGACY_DATETIME => Ok(SchemaType::Timestamp(TimePrecision::Micro)),
+                    v => SchemaType::deserialize(v.into_deserializer()),
+                }
+            }
+
+            fn visit_enum<A: serde::de::EnumAccess<'de>>(
+                self,
+                data: A,
+            ) -> Result<SchemaType, A::Error> {
+                SchemaType::deserialize(serde::de::value::EnumAccessDeserializer::new(data))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<SchemaType, A::Error> {
+                SchemaType::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+            }
+        }
+
+        deserializer.deserialize_any(SchemaTypeVisitor)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum SchemaTypeWithValue {
     String(String),
     Int32(i32),
     Int64(i64),
-    Binary(Vec<char>),
+    Binary(Vec<u8>),
     Boolean(bool),
-    Timestamp(u32),
-    Date(DateTime<Utc>),
-    Datetime(DateTime<Utc>),
+    Timestamp(NaiveDateTime),
+    Timestamptz(DateTime<Utc>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Interval(Interval),
     Double(f64),
     Float(f32),
+    Decimal(BigDecimal),
+    Uuid(Uuid),
+    Json(Value),
+    List(Vec<SchemaTypeWithValue>),
+    Struct(Vec<Column>),
     None,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// fractional second precision of timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TimePrecision {
+    Micro,
+    Nano,
+}
+
+impl TimePrecision {
+    // coarsest precision which keeps the nanoseconds of value.
+    fn of(nanosecond: u32) -> Self {
+        if nanosecond.is_multiple_of(1000) {
+            TimePrecision::Micro
+        } else {
+            TimePrecision::Nano
+        }
+    }
+}
+
+/// interval as postgres keeps it, months and days have no fixed length so they are not folded
+/// into microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub microseconds: i64,
+}
+
+impl Interval {
+    /// ISO 8601 duration, e.g. `P1Y2M3DT4H5M6.5S`, which postgres accepts as input.
+    pub fn to_iso8601(self) -> String {
+        let mut text = "P".to_string();
+        for (value, designator) in [
+            (i64::from(self.months / 12), 'Y'),
+            (i64::from(self.months % 12), 'M'),
+            (i64::from(self.days), 'D'),
+        ] {
+            if value != 0 {
+                text.push_str(&format!("{value}{designator}"));
+            }
+        }
+        let hours = self.microseconds / 3_600_000_000;
+        let minutes = self.microseconds % 3_600_000_000 / 60_000_000;
+        let micros = self.microseconds % 60_000_000;
+        if self.microseconds != 0 {
+            text.push('T');
+            for (value, designator) in [(hours, 'H'), (minutes, 'M')] {
+                if value != 0 {
+                    text.push_str(&format!("{value}{designator}"));
+                }
+            }
+            if micros != 0 {
+                let sign = if micros < 0 { "-" } else { "" };
+                let micros = micros.unsigned_abs();
+                let seconds = format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000);
+                text.push_str(&format!(
+                    "{sign}{}S",
+                    seconds.trim_end_matches('0').trim_end_matches('.')
+                ));
+            }
+        }
+        if text == "P" {
+            text.push_str("T0S");
+        }
+        text
+    }
+}
+
+// decimal keeps value if it has at most `scale` fractional digits and `precision - scale` integer
+// digits.
+fn decimal_fits(value: &BigDecimal, precision: u16, scale: i16) -> bool {
+    let (value_precision, value_scale) = decimal_precision_scale(value);
+    value_scale <= scale
+        && i32::from(value_precision) - i32::from(value_scale)
+            <= i32::from(precision) - i32::from(scale)
+}
+
+/// plain value of json, numbers are Int64 if integral, arrays and objects are nested values.
+pub fn from_json(value: &Value) -> SchemaTypeWithValue {
+    match value {
+        Value::Null => SchemaTypeWithValue::None,
+        Value::Bool(b) => SchemaTypeWithValue::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SchemaTypeWithValue::Int64(i),
+            None => n
+                .as_f64()
+                .map_or(SchemaTypeWithValue::None, SchemaTypeWithValue::Double),
+        },
+        Value::String(s) => SchemaTypeWithValue::String(s.clone()),
+        Value::Array(values) => SchemaTypeWithValue::List(values.iter().map(from_json).collect()),
+        Value::Object(map) => SchemaTypeWithValue::Struct(
+            map.iter()
+                .map(|(name, value)| Column {
+                    name: name.clone(),
+                    value: from_json(value),
+                })
+                .collect(),
+        ),
+    }
+}
+
+// compiled once, it is matched by every lossy cast of text.
+static NUMERIC_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?").unwrap());
+
+// leading number of text, e.g. `12` of `12abc`.
+fn numeric_prefix(s: &str) -> Option<&str> {
+    NUMERIC_PREFIX.find(s.trim()).map(|m| m.as_str())
+}
+
+// timestamp in text, `T` or space separates date and time, date only is at midnight.
+fn parse_timestamp(s: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .map(|d| d.and_time(NaiveTime::MIN))
+        })
+}
+
+/// text form of binary values in text formats, from `binary_encoding` in options of sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum BinaryEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+impl BinaryEncoding {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Result<Self> {
+        match options
+            .get("binary_encoding")
+            .map(|encoding| encoding.to_lowercase())
+            .as_deref()
+        {
+            None | Some("hex") => Ok(BinaryEncoding::Hex),
+            Some("base64") => Ok(BinaryEncoding::Base64),
+            Some(unk) => Err(ParameterError::new(
+                format!("`binary_encoding` must be `hex` or `base64`, got `{unk}`").as_str(),
+            )
+            .into()),
+        }
+    }
+
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            BinaryEncoding::Hex => bytes.iter().map(|b| format!("{b:02x}")).collect(),
+            BinaryEncoding::Base64 => BASE64.encode(bytes),
+        }
+    }
+}
+
+/// how values are cast to the type of their column in sink schema, set by `coercion` in config
+/// or in `extra` of a field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoercionPolicy {
+    /// value must already be of the type, e.g. Int64 is not allowed in Int32.
+    Strict,
+    /// casts which keep value only, e.g. `"12"` into Int32 but not `"12abc"` or `1.5`.
+    #[default]
+    Safe,
+    /// round or truncate values which do not fit, e.g. `1.5` into Int32 is 2 and `"12abc"` is 12.
+    Lossy,
+}
+
+impl std::fmt::Display for CoercionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            CoercionPolicy::Strict => "strict",
+            CoercionPolicy::Safe => "safe",
+            CoercionPolicy::Lossy => "lossy",
+        })
+    }
+}
+
+impl FromStr for CoercionPolicy {
+    type Err = ParameterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" => Ok(CoercionPolicy::Strict),
+            "safe" => Ok(CoercionPolicy::Safe),
+            "lossy" => Ok(CoercionPolicy::Lossy),
+            unk => Err(ParameterError::new(
+                format!("coercion must be `strict`, `safe` or `lossy`, got `{unk}`").as_str(),
+            )),
+        }
+    }
+}
+
+impl SchemaType {
+    /// metadata implied by type, it is kept in `extra` of `SchemaField`.
+    pub fn extra(&self) -> HashMap<String, String> {
+        match self {
+            SchemaType::Decimal(0, 0) => HashMap::new(),
+            SchemaType::Decimal(precision, scale) => HashMap::from([
+                ("precision".to_string(), precision.to_string()),
+                ("scale".to_string(), scale.to_string()),
+            ]),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+/// precision and scale of decimal, e.g. `12e3` has precision 5 and scale 0, `0.001` has
+/// precision 3 and scale 3.
+pub fn decimal_precision_scale(value: &BigDecimal) -> (u16, i16) {
+    let (_, scale) = value.as_bigint_and_exponent();
+    let digits = i64::try_from(value.digits()).unwrap_or(i64::MAX);
+    let precision = (digits - scale.min(0)).max(scale).max(1);
+    (
+        u16::try_from(precision).unwrap_or(u16::MAX),
+        i16::try_from(scale.max(0)).unwrap_or(i16::MAX),
+    )
+}
+
+impl SchemaTypeWithValue {
+    pub fn schema_type(&self) -> SchemaType {
+        match self {
+            SchemaTypeWithValue::String(_) => SchemaType::String,
+            SchemaTypeWithValue::Int32(_) => SchemaType::Int32,
+            SchemaTypeWithValue::Int64(_) => SchemaType::Int64,
+            SchemaTypeWithValue::Binary(_) => SchemaType::Binary,
+            SchemaTypeWithValue::Boolean(_) => SchemaType::Boolean,
+            SchemaTypeWithValue::Timestamp(t) => {
+                SchemaType::Timestamp(TimePrecision::of(t.nanosecond()))
+            }
+            SchemaTypeWithValue::Timestamptz(t) => {
+                SchemaType::Timestamptz(TimePrecision::of(t.nanosecond()))
+            }
+            SchemaTypeWithValue::Date(_) => SchemaType::Date,
+            SchemaTypeWithValue::Time(_) => SchemaType::Time,
+            SchemaTypeWithValue::Interval(_) => SchemaType::Interval,
+            SchemaTypeWithValue::Double(_) => SchemaType::Double,
+            SchemaTypeWithValue::Float(_) => SchemaType::Float,
+            SchemaTypeWithValue::Decimal(d) => {
+                let (precision, scale) = decimal_precision_scale(d);
+                SchemaType::Decimal(precision, scale)
+            }
+            SchemaTypeWithValue::Uuid(_) => SchemaType::Uuid,
+            SchemaTypeWithValue::Json(_) => SchemaType::Json,
+            SchemaTypeWithValue::List(values) => SchemaType::List(Box::new(
+                values.iter().fold(SchemaType::None, |type_, value| {
+                    merge_type(&type_, &value.schema_type())
+                }),
+            )),
+            SchemaTypeWithValue::Struct(columns) => {
+                SchemaType::Struct(Row(columns.clone()).schema().0)
+            }
+            SchemaTypeWithValue::None => SchemaType::None,
+        }
+    }
+
+    /// uuid of value, strings are parsed and binary must be 16 bytes.
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            SchemaTypeWithValue::Uuid(u) => Some(*u),
+            SchemaTypeWithValue::String(s) => Uuid::parse_str(s).ok(),
+            SchemaTypeWithValue::Binary(b) => Uuid::from_slice(b).ok(),
+            _ => None,
+        }
+    }
+
+    /// value as integer if it is one, e.g. `2.0` and `"2"` but not `2.5`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            SchemaTypeWithValue::Int32(i) => Some(i64::from(*i)),
+            SchemaTypeWithValue::Int64(i) => Some(*i),
+            SchemaTypeWithValue::Double(f) if f.fract() == 0.0 => f.to_i64(),
+            SchemaTypeWithValue::Float(f) if f.fract() == 0.0 => f.to_i64(),
+            SchemaTypeWithValue::Decimal(d) if d.is_integer() => d.to_i64(),
+            SchemaTypeWithValue::String(s) => s.trim().parse::<i64>().ok(),
+            _ => None,
+        }
+    }
+
+    /// value as double if it is exactly representable.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            SchemaTypeWithValue::Int32(i) => Some(f64::from(*i)),
+            SchemaTypeWithValue::Int64(i) => {
+                let f = *i as f64;
+                (f.to_i64() == Some(*i)).then_some(f)
+            }
+            SchemaTypeWithValue::Double(f) => Some(*f),
+            SchemaTypeWithValue::Float(f) => Some(f64::from(*f)),
+            SchemaTypeWithValue::Decimal(d) => {
+                let f = d.to_f64()?;
+                (BigDecimal::from_str(&f.to_string()).ok()? == *d).then_some(f)
+            }
+            SchemaTypeWithValue::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        }
+    }
+
+    /// value as decimal, floats are taken by their shortest text.
+    pub fn as_decimal(&self) -> Option<BigDecimal> {
+        match self {
+            SchemaTypeWithValue::Int32(i) => Some(BigDecimal::from(*i)),
+            SchemaTypeWithValue::Int64(i) => Some(BigDecimal::from(*i)),
+            SchemaTypeWithValue::Double(f) => BigDecimal::from_str(&f.to_string()).ok(),
+            SchemaTypeWithValue::Float(f) => BigDecimal::from_str(&f.to_string()).ok(),
+            SchemaTypeWithValue::Decimal(d) => Some(d.clone()),
+            SchemaTypeWithValue::String(s) => BigDecimal::from_str(s.trim()).ok(),
+            _ => None,
+        }
+    }
+
+    /// order of values of the same kind, numbers are compared by value. None if they cannot be
+    /// compared, e.g. nulls or values of different kinds.
+    pub fn compare(&self, other: &SchemaTypeWithValue) -> Option<Ordering> {
+        use SchemaTypeWithValue as V;
+        match (self, other) {
+            (V::String(a), V::String(b)) => Some(a.cmp(b)),
+            (V::Date(a), V::Date(b)) => Some(a.cmp(b)),
+            (V::Time(a), V::Time(b)) => Some(a.cmp(b)),
+            (V::Timestamp(a), V::Timestamp(b)) => Some(a.cmp(b)),
+            (V::Timestamptz(a), V::Timestamptz(b)) => Some(a.cmp(b)),
+            (
+                V::Int32(_) | V::Int64(_) | V::Double(_) | V::Float(_) | V::Decimal(_),
+                V::Int32(_) | V::Int64(_) | V::Double(_) | V::Float(_) | V::Decimal(_),
+            ) => self.as_decimal()?.partial_cmp(&other.as_decimal()?),
+            _ => None,
+        }
+    }
+
+    /// value converted to `type_` without loss, text is parsed and json is taken apart for nested
+    /// types. None if the value does not fit, null is kept for every type.
+    pub fn cast(&self, type_: &SchemaType) -> Option<SchemaTypeWithValue> {
+        let value = match (type_, self) {
+            (_, SchemaTypeWithValue::None) | (SchemaType::None, _) => self.clone(),
+            (SchemaType::String, value) => SchemaTypeWithValue::String(value.to_text()?),
+            (SchemaType::Int32, value) => {
+                SchemaTypeWithValue::Int32(i32::try_from(value.as_i64()?).ok()?)
+            }
+            (SchemaType::Int64, value) => SchemaTypeWithValue::Int64(value.as_i64()?),
+            (SchemaType::Double, value) => SchemaTypeWithValue::Double(value.as_f64()?),
+            (SchemaType::Float, value) => {
+                let f = value.as_f64()?;
+                let narrowed = f as f32;
+                if f64::from(narrowed) != f && !f.is_nan() {
+                    return None;
+                }
+                SchemaTypeWithValue::Float(narrowed)
+            }
+            (SchemaType::Decimal(0, 0), value) => SchemaTypeWithValue::Decimal(value.as_decimal()?),
+            (SchemaType::Decimal(precision, scale), value) => {
+                let d = value.as_decimal()?;
+                if !decimal_fits(&d, *precision, *scale) {
+                    return None;
+                }
+                SchemaTypeWithValue::Decimal(d.with_scale(i64::from(*scale)))
+            }
+            (SchemaType::Boolean, SchemaTypeWithValue::Boolean(b)) => {
+                SchemaTypeWithValue::Boolean(*b)
+            }
+            (SchemaType::Boolean, SchemaTypeWithValue::String(s)) => {
+                match s.trim().to_lowercase().as_str() {
+                    "true" | "t" | "1" => SchemaTypeWithValue::Boolean(true),
+                    "false" | "f" | "0" => SchemaTypeWithValue::Boolean(false),
+                    _ => return None,
+                }
+            }
+            (SchemaType::Boolean, value) => match value.as_i64()? {
+                0 => SchemaTypeWithValue::Boolean(false),
+                1 => SchemaTypeWithValue::Boolean(true),
+                _ => return None,
+            },
+            (SchemaType::Binary, SchemaTypeWithValue::Binary(b)) => {
+                SchemaTypeWithValue::Binary(b.clone())
+            }
+            (SchemaType::Binary, SchemaTypeWithValue::String(s)) => {
+                SchemaTypeWithValue::Binary(s.as_bytes().to_vec())
+            }
+            (SchemaType::Binary, SchemaTypeWithValue::Uuid(u)) => {
+                SchemaTypeWithValue::Binary(u.as_bytes().to_vec())
+            }
+            (SchemaType::Uuid, value) => SchemaTypeWithValue::Uuid(value.as_uuid()?),
+            (SchemaType::Json, SchemaTypeWithValue::String(s)) => {
+                SchemaTypeWithValue::Json(serde_json::from_str(s).ok()?)
+            }
+            (SchemaType::Json, value) => SchemaTypeWithValue::Json(value.to_json()),
+            (SchemaType::Date, SchemaTypeWithValue::Date(d)) => SchemaTypeWithValue::Date(*d),
+            (SchemaType::Date, SchemaTypeWithValue::String(s)) => {
+                SchemaTypeWithValue::Date(NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok()?)
+            }
+            (SchemaType::Date, SchemaTypeWithValue::Timestamp(t)) if t.time() == NaiveTime::MIN => {
+                SchemaTypeWithValue::Date(t.date())
+            }
+            (SchemaType::Time, SchemaTypeWithValue::Time(t)) => SchemaTypeWithValue::Time(*t),
+            (SchemaType::Time, SchemaTypeWithValue::String(s)) => {
+                SchemaTypeWithValue::Time(NaiveTime::from_str(s.trim()).ok()?)
+            }
+            (SchemaType::Timestamp(precision), value) => {
+                let t = match value {
+                    SchemaTypeWithValue::Timestamp(t) => *t,
+                    SchemaTypeWithValue::Timestamptz(t) => t.naive_utc(),
+                    SchemaTypeWithValue::Date(d) => d.and_time(NaiveTime::MIN),
+                    SchemaTypeWithValue::String(s) => parse_timestamp(s.trim())?,
+                    _ => return None,
+                };
+                if TimePrecision::of(t.nanosecond()) > *precision {
+                    return None;
+                }
+                SchemaTypeWithValue::Timestamp(t)
+            }
+            // naive values have no time zone, they are taken as UTC only by lossy casts.
+            (SchemaType::Timestamptz(precision), value) => {
+                let t = match value {
+                    SchemaTypeWithValue::Timestamptz(t) => *t,
+                    SchemaTypeWithValue::String(s) => DateTime::parse_from_rfc3339(s.trim())
+                        .ok()?
+                        .with_timezone(&Utc),
+                    _ => return None,
+                };
+                if TimePrecision::of(t.nanosecond()) > *precision {
+                    return None;
+                }
+                SchemaTypeWithValue::Timestamptz(t)
+            }
+            (SchemaType::Interval, SchemaTypeWithValue::Interval(i)) => {
+                SchemaTypeWithValue::Interval(*i)
+            }
+            (SchemaType::List(inner), SchemaTypeWithValue::List(values)) => {
+                SchemaTypeWithValue::List(
+                    values
+                        .iter()
+                        .map(|value| value.cast(inner))
+                        .collect::<Option<Vec<_>>>()?,
+                )
+            }
+            (SchemaType::Struct(fields), SchemaTypeWithValue::Struct(columns)) => {
+                SchemaTypeWithValue::Struct(
+                    Row(columns.clone())
+                        .normalize(&Schema(fields.clone()), CoercionPolicy::Safe)
+                        .ok()?
+                        .0
+                         .0,
+                )
+            }
+            (SchemaType::List(_) | SchemaType::Struct(_), SchemaTypeWithValue::String(s)) => {
+                SchemaTypeWithValue::Json(serde_json::from_str(s).ok()?).cast(type_)?
+            }
+            (_, SchemaTypeWithValue::Json(j)) => from_json(j).cast(type_)?,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// value converted to `type_` under `policy`, with the number of values which were changed by
+    /// lossy casts. None if the value cannot be converted.
+    pub fn coerce(
+        &self,
+        type_: &SchemaType,
+        policy: CoercionPolicy,
+    ) -> Option<(SchemaTypeWithValue, usize)> {
+        match (type_, self) {
+            (_, SchemaTypeWithValue::None) | (SchemaType::None, _) => Some((self.clone(), 0)),
+            (SchemaType::List(inner), SchemaTypeWithValue::List(values)) => {
+                let mut lossy = 0;
+                let values = values
+                    .iter()
+                    .map(|value| {
+                        let (value, value_lossy) = value.coerce(inner, policy)?;
+                        lossy += value_lossy;
+                        Some(value)
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some((SchemaTypeWithValue::List(values), lossy))
+            }
+            (SchemaType::Struct(fields), SchemaTypeWithValue::Struct(columns)) => {
+                let (row, lossy) = Row(columns.clone())
+                    .normalize(&Schema(fields.clone()), policy)
+                    .ok()?;
+                Some((SchemaTypeWithValue::Struct(row.0), lossy))
+            }
+            (SchemaType::List(_) | SchemaType::Struct(_), SchemaTypeWithValue::String(s))
+                if policy != CoercionPolicy::Strict =>
+            {
+                from_json(&serde_json::from_str(s).ok()?).coerce(type_, policy)
+            }
+            (SchemaType::List(_) | SchemaType::Struct(_), SchemaTypeWithValue::Json(j))
+                if policy != CoercionPolicy::Strict =>
+            {
+                from_json(j).coerce(type_, policy)
+            }
+            _ => match policy {
+                CoercionPolicy::Strict => (mem::discriminant(&self.schema_type())
+                    == mem::discriminant(type_))
+                .then(|| self.cast(type_))?
+                .map(|value| (value, 0)),
+                CoercionPolicy::Safe => self.cast(type_).map(|value| (value, 0)),
+                CoercionPolicy::Lossy => match self.cast(type_) {
+                    Some(value) => Some((value, 0)),
+                    None => self.lossy_cast(type_).map(|value| (value, 1)),
+                },
+            },
+        }
+    }
+
+    // value forced into `type_`, numbers are rounded or saturated, text is cut to its leading
+    // number, timestamps are truncated to microseconds and naive ones are taken as UTC.
+    fn lossy_cast(&self, type_: &SchemaType) -> Option<SchemaTypeWithValue> {
+        let lossy_f64 = || match self {
+            SchemaTypeWithValue::Int64(i) => Some(*i as f64),
+            SchemaTypeWithValue::Decimal(d) => d.to_f64(),
+            SchemaTypeWithValue::Boolean(b) => Some(f64::from(u8::from(*b))),
+            SchemaTypeWithValue::String(s) => numeric_prefix(s)?.parse::<f64>().ok(),
+            value => value.as_f64(),
+        };
+        let lossy_decimal = || match self {
+            SchemaTypeWithValue::String(s) => BigDecimal::from_str(numeric_prefix(s)?).ok(),
+            value => value.as_decimal(),
+        };
+        let micro = TimeDelta::microseconds(1);
+        let value = match type_ {
+            SchemaType::Int32 => {
+                let f = lossy_f64()?.round();
+                SchemaTypeWithValue::Int32((!f.is_nan()).then_some(f as i32)?)
+            }
+            SchemaType::Int64 => match lossy_decimal() {
+                Some(d) => {
+                    let saturated = if d.is_negative() { i64::MIN } else { i64::MAX };
+                    SchemaTypeWithValue::Int64(d.round(0).to_i64().unwrap_or(saturated))
+                }
+                None => {
+                    let f = lossy_f64()?.round();
+                    SchemaTypeWithValue::Int64((!f.is_nan()).then_some(f as i64)?)
+                }
+            },
+            SchemaType::Double => SchemaTypeWithValue::Double(lossy_f64()?),
+            SchemaType::Float => SchemaTypeWithValue::Float(lossy_f64()? as f32),
+            SchemaType::Decimal(0, 0) => SchemaTypeWithValue::Decimal(lossy_decimal()?),
+            SchemaType::Decimal(_, scale) => {
+                SchemaTypeWithValue::Decimal(lossy_decimal()?.round(i64::from(*scale)))
+                    .cast(type_)?
+            }
+            SchemaType::Boolean => SchemaTypeWithValue::Boolean(lossy_f64()? != 0.0),
+            SchemaType::Date => match self.cast(&SchemaType::Timestamp(TimePrecision::Nano))? {
+                SchemaTypeWithValue::Timestamp(t) => SchemaTypeWithValue::Date(t.date()),
+                _ => return None,
+            },
+            SchemaType::Timestamp(_) => {
+                match self.cast(&SchemaType::Timestamp(TimePrecision::Nano))? {
+                    SchemaTypeWithValue::Timestamp(t) => {
+                        SchemaTypeWithValue::Timestamp(t.duration_trunc(micro).ok()?)
+                    }
+                    _ => return None,
+                }
+            }
+            SchemaType::Timestamptz(_) => {
+                let t = match self.cast(&SchemaType::Timestamptz(TimePrecision::Nano)) {
+                    Some(SchemaTypeWithValue::Timestamptz(t)) => t,
+                    _ => match self.cast(&SchemaType::Timestamp(TimePrecision::Nano))? {
+                        SchemaTypeWithValue::Timestamp(t) => t.and_utc(),
+                        _ => return None,
+                    },
+                };
+                SchemaTypeWithValue::Timestamptz(t.duration_trunc(micro).ok()?)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// json of value, nested values are kept as arrays and objects, others which have no json
+    /// counterpart are their text.
+    pub fn to_json(&self) -> Value {
+        self.encode_json(BinaryEncoding::Hex)
+    }
+
+    /// like `to_json`, binary values are encoded by `binary`.
+    pub fn encode_json(&self, binary: BinaryEncoding) -> Value {
+        match self {
+            SchemaTypeWithValue::String(s) => Value::String(s.clone()),
+            SchemaTypeWithValue::Int32(i) => Value::from(*i),
+            SchemaTypeWithValue::Int64(i) => Value::from(*i),
+            SchemaTypeWithValue::Boolean(b) => Value::Bool(*b),
+            SchemaTypeWithValue::Double(f) => {
+                Number::from_f64(*f).map_or(Value::Null, Value::Number)
+            }
+            SchemaTypeWithValue::Float(f) => {
+                Number::from_f64(f64::from(*f)).map_or(Value::Null, Value::Number)
+            }
+            SchemaTypeWithValue::Json(j) => j.clone(),
+            SchemaTypeWithValue::List(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| value.encode_json(binary))
+                    .collect(),
+            ),
+            SchemaTypeWithValue::Struct(columns) => Value::Object(
+                columns
+                    .iter()
+                    .map(|column| (column.name.clone(), column.value.encode_json(binary)))
+                    .collect::<Map<_, _>>(),
+            ),
+            SchemaTypeWithValue::None => Value::Null,
+            other => other.encode_text(binary).map_or(Value::Null, Value::String),
+        }
+    }
+
+    /// canonical text form of value, None means null. Binary values are in hex.
+    pub fn to_text(&self) -> Option<String> {
+        self.encode_text(BinaryEncoding::Hex)
+    }
+
+    /// like `to_text`, binary values are encoded by `binary`.
+    pub fn encode_text(&self, binary: BinaryEncoding) -> Option<String> {
+        match self {
+            SchemaTypeWithValue::String(s) => Some(s.clone()),
+            SchemaTypeWithValue::Int32(i) => Some(i.to_string()),
+            SchemaTypeWithValue::Int64(i) => Some(i.to_string()),
+            SchemaTypeWithValue::Binary(b) => Some(binary.encode(b)),
+            SchemaTypeWithValue::Boolean(b) => Some(b.to_string()),
+            SchemaTypeWithValue::Timestamp(t) => Some(t.format("%Y-%m-%dT%H:%M:%S%.f").to_string()),
+            SchemaTypeWithValue::Timestamptz(t) => {
+                Some(t.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            SchemaTypeWithValue::Date(d) => Some(d.format("%Y-%m-%d").to_string()),
+            SchemaTypeWithValue::Time(t) => Some(t.format("%H:%M:%S%.f").to_string()),
+            SchemaTypeWithValue::Interval(i) => Some(i.to_iso8601()),
+            SchemaTypeWithValue::Double(f) => Some(f.to_string()),
+            SchemaTypeWithValue::Float(f) => Some(f.to_string()),
+            SchemaTypeWithValue::Decimal(d) => Some(d.to_string()),
+            SchemaTypeWithValue::Uuid(u) => Some(u.hyphenated().to_string()),
+            SchemaTypeWithValue::Json(j) => Some(j.to_string()),
+            SchemaTypeWithValue::List(_) | SchemaTypeWithValue::Struct(_) => {
+                Some(self.encode_json(binary).to_string())
+            }
+            SchemaTypeWithValue::None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SchemaField {
     pub name: String,
     #[serde(rename(serialize = "type", deserialize = "type"))]
@@ -45,18 +763,86 @@ pub struct SchemaField {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Schema(pub Vec<SchemaField>);
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Column {
     pub name: String,
     pub value: SchemaTypeWithValue,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Row(pub Vec<Column>);
 impl Row {
-    pub fn normalize(&self, schema: Schema) -> Row {
-        // TODO: normailze
-        self.clone()
+    pub fn get(&self, name: &str) -> Option<&SchemaTypeWithValue> {
+        self.0
+            .iter()
+            .find(|column| column.name == name)
+            .map(|column| &column.value)
+    }
+
+    // schema inferred from values of this row only.
+    pub fn schema(&self) -> Schema {
+        Schema(
+            self.0
+                .iter()
+                .map(|column| {
+                    let type_ = column.value.schema_type();
+                    SchemaField {
+                        name: column.name.clone(),
+                        extra: type_.extra(),
+                        type_,
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// row with columns in the order of `schema` and values cast to their types, columns not in
+    /// `schema` are dropped and missing ones are null. Columns with `nullable` of `false` in extra
+    /// must have a value. Values are cast by `policy` or `coercion` in extra of their field, the
+    /// number of lossy casts is returned with the row.
+    pub fn normalize(
+        &self,
+        schema: &Schema,
+        policy: CoercionPolicy,
+    ) -> Result<(Row, usize), CastError> {
+        let mut lossy = 0;
+        let columns = schema
+            .0
+            .iter()
+            .map(|field| {
+                let value = self.get(&field.name).unwrap_or(&SchemaTypeWithValue::None);
+                let nullable = field.extra.get("nullable").map(String::as_str) != Some("false");
+                let policy = match field.extra.get("coercion") {
+                    Some(coercion) => coercion
+                        .parse::<CoercionPolicy>()
+                        .map_err(|err| CastError::new(&field.name, &err.to_string()))?,
+                    None => policy,
+                };
+                match value.coerce(&field.type_, policy) {
+                    Some((SchemaTypeWithValue::None, _)) if !nullable => Err(CastError::new(
+                        &field.name,
+                        "null value in non-nullable column",
+                    )),
+                    Some((value, value_lossy)) => {
+                        lossy += value_lossy;
+                        Ok(Column {
+                            name: field.name.clone(),
+                            value,
+                        })
+                    }
+                    None => Err(CastError::new(
+                        &field.name,
+                        format!(
+                            "cannot cast `{}` to {:?} by {policy:?} coercion",
+                            value.to_text().unwrap_or_default(),
+                            field.type_
+                        )
+                        .as_str(),
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((Row(columns), lossy))
     }
 }
 
@@ -70,6 +856,8 @@ pub struct ReadResult {
 pub trait DataStorage {
     async fn read_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Schema>;
 
+    /// read all data, storages which stream large results may return them in several parts, the
+    /// `cursor` of result is `Some` while there is more data and `read` should be called again.
     async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult>;
 
     async fn chunk_read(
@@ -79,10 +867,251 @@ pub trait DataStorage {
         options: &HashMap<&str, &str>,
     ) -> Result<ReadResult>;
 
+    /// whether reads only return rows after `watermark` of `incremental_column` in options, other
+    /// storages ignore them.
+    fn incremental(&self) -> bool {
+        false
+    }
+
+    /// whether several instances of the storage could write into the same sink concurrently, e.g.
+    /// databases. Sinks written by one instance only(e.g. files) are shared by writers.
+    fn concurrent_write(&self) -> bool {
+        false
+    }
+
+    /// whether rows are persisted once `write` returns and writes continue after the existing
+    /// data with `append=true`, which are required to resume a chunk trans. Sinks which buffer
+    /// rows until closed(e.g. parquet) or always truncate cannot be resumed.
+    fn resumable(&self) -> bool {
+        false
+    }
+
+    /// options of parts which could be chunk read concurrently, each by its own instance of the
+    /// storage. A single part of the same options by default.
+    async fn partition(
+        &mut self,
+        options: &HashMap<&str, &str>,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        Ok(vec![options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()])
+    }
+
+    /// acknowledge rows read after all of them are written into sink, sources which track
+    /// consumed positions(e.g. groups of kafka) commit them. Nothing to commit by default.
+    async fn commit(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     async fn write(
         &mut self,
         data: Vec<Row>,
         schema: Option<Schema>,
         options: &HashMap<&str, &str>,
     ) -> Result<()>;
+
+    /// flush and release the sink after all writes, storages which write footers(e.g. parquet)
+    /// are incomplete until closed.
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Coerced = Option<(SchemaTypeWithValue, usize)>;
+
+    #[test]
+    fn schema_type_serde() {
+        let types = vec![
+            SchemaType::Int64,
+            SchemaType::Timestamp(TimePrecision::Nano),
+            SchemaType::Decimal(10, 2),
+            SchemaType::List(Box::new(SchemaType::Int64)),
+            SchemaType::Struct(vec![SchemaField {
+                name: "at".to_string(),
+                type_: SchemaType::Date,
+                extra: HashMap::new(),
+            }]),
+        ];
+        let yaml = serde_yaml::to_string(&types).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Vec<SchemaType>>(&yaml).unwrap(),
+            types
+        );
+        let json = serde_json::to_string(&types).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<SchemaType>>(&json).unwrap(),
+            types
+        );
+
+        // `Datetime` of old configs.
+        let micro = SchemaType::Timestamp(TimePrecision::Micro);
+        assert_eq!(
+            serde_yaml::from_str::<Vec<SchemaType>>("- Datetime\n- !List Datetime").unwrap(),
+            vec![micro.clone(), SchemaType::List(Box::new(micro.clone()))]
+        );
+        assert_eq!(
+            serde_json::from_str::<SchemaType>(r#""Datetime""#).unwrap(),
+            micro
+        );
+        assert!(serde_yaml::from_str::<SchemaType>("Datetimes").is_err());
+    }
+
+    #[test]
+    fn interval_to_iso8601() {
+        // postgres reads each of them back to the same interval.
+        for (months, days, microseconds, expected) in [
+            (14, -3, 14_706_500_000, "P1Y2M-3DT4H5M6.5S"),
+            (0, 0, -14_706_000_001, "PT-4H-5M-6.000001S"),
+            (0, 0, 0, "PT0S"),
+            (-14, 0, 0, "P-1Y-2M"),
+            (0, 1, -500_000, "P1DT-0.5S"),
+        ] {
+            let interval = Interval {
+                months,
+                days,
+                microseconds,
+            };
+            assert_eq!(interval.to_iso8601(), expected);
+        }
+    }
+
+    #[test]
+    fn coerce_by_policy() {
+        use SchemaTypeWithValue as V;
+        let int32 = |i| Some((V::Int32(i), 0));
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let midnight = date.and_time(NaiveTime::MIN);
+        // value, target type, then the result of strict, safe and lossy coercion.
+        let cases: Vec<(V, SchemaType, [Coerced; 3])> = vec![
+            (
+                V::Int32(5),
+                SchemaType::Int32,
+                [int32(5), int32(5), int32(5)],
+            ),
+            (V::Int64(5), SchemaType::Int32, [None, int32(5), int32(5)]),
+            (
+                V::String("12".to_string()),
+                SchemaType::Int32,
+                [None, int32(12), int32(12)],
+            ),
+            (
+                V::String("12abc".to_string()),
+                SchemaType::Int32,
+                [None, None, Some((V::Int32(12), 1))],
+            ),
+            (
+                V::Double(1.5),
+                SchemaType::Int32,
+                [None, None, Some((V::Int32(2), 1))],
+            ),
+            (
+                V::Int64(1 << 40),
+                SchemaType::Int32,
+                [None, None, Some((V::Int32(i32::MAX), 1))],
+            ),
+            (
+                V::Double(1e30),
+                SchemaType::Int64,
+                [None, None, Some((V::Int64(i64::MAX), 1))],
+            ),
+            (
+                V::Double(-1e30),
+                SchemaType::Int64,
+                [None, None, Some((V::Int64(i64::MIN), 1))],
+            ),
+            (
+                V::Double(0.1),
+                SchemaType::Float,
+                [None, None, Some((V::Float(0.1), 1))],
+            ),
+            (
+                V::String("2024-01-02".to_string()),
+                SchemaType::Date,
+                [None, Some((V::Date(date), 0)), Some((V::Date(date), 0))],
+            ),
+            (
+                V::Timestamp(midnight),
+                SchemaType::Timestamptz(TimePrecision::Micro),
+                [None, None, Some((V::Timestamptz(midnight.and_utc()), 1))],
+            ),
+            (
+                V::String("2024-01-02T00:00:00+01:00".to_string()),
+                SchemaType::Timestamptz(TimePrecision::Micro),
+                [
+                    None,
+                    Some((
+                        V::Timestamptz((midnight - TimeDelta::hours(1)).and_utc()),
+                        0,
+                    )),
+                    Some((
+                        V::Timestamptz((midnight - TimeDelta::hours(1)).and_utc()),
+                        0,
+                    )),
+                ],
+            ),
+            (
+                V::String("[1, 2]".to_string()),
+                SchemaType::List(Box::new(SchemaType::Int32)),
+                [
+                    None,
+                    Some((V::List(vec![V::Int32(1), V::Int32(2)]), 0)),
+                    Some((V::List(vec![V::Int32(1), V::Int32(2)]), 0)),
+                ],
+            ),
+            (
+                V::String("x".to_string()),
+                SchemaType::Uuid,
+                [None, None, None],
+            ),
+            (
+                V::None,
+                SchemaType::Int32,
+                [Some((V::None, 0)), Some((V::None, 0)), Some((V::None, 0))],
+            ),
+        ];
+        let policies = [
+            CoercionPolicy::Strict,
+            CoercionPolicy::Safe,
+            CoercionPolicy::Lossy,
+        ];
+        for (value, type_, expected) in cases {
+            for (policy, expected) in policies.iter().zip(expected) {
+                assert_eq!(
+                    value.coerce(&type_, *policy),
+                    expected,
+                    "{value:?} into {type_:?} by {policy:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn normalize_missing_columns() {
+        let field = |name: &str, nullable: &str| SchemaField {
+            name: name.to_string(),
+            type_: SchemaType::Int32,
+            extra: HashMap::from([("nullable".to_string(), nullable.to_string())]),
+        };
+        let row = Row(vec![Column {
+            name: "a".to_string(),
+            value: SchemaTypeWithValue::Int64(1),
+        }]);
+        for policy in [
+            CoercionPolicy::Strict,
+            CoercionPolicy::Safe,
+            CoercionPolicy::Lossy,
+        ] {
+            let nullable = Schema(vec![field("b", "true")]);
+            let (normalized, lossy) = row.normalize(&nullable, policy).unwrap();
+            assert_eq!(normalized.get("b"), Some(&SchemaTypeWithValue::None));
+            assert_eq!(lossy, 0);
+            let not_null = Schema(vec![field("b", "false")]);
+            assert!(row.normalize(&not_null, policy).is_err(), "{policy:?}");
+        }
+    }
 }
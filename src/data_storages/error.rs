@@ -0,0 +1,58 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub struct ParameterError {
+    reason: String,
+}
+impl std::error::Error for ParameterError {}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "parameter error: {}", self.reason)
+    }
+}
+impl ParameterError {
+    pub fn new(reason: &str) -> ParameterError {
+        ParameterError {
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// value which cannot be cast to the type of its column, `row` is counted from 0 in the source.
+#[derive(Debug)]
+pub struct CastError {
+    pub row: Option<usize>,
+    pub column: String,
+    reason: String,
+}
+impl std::error::Error for CastError {}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.row {
+            Some(row) => write!(
+                f,
+                "cast error at row {row}, column `{}`: {}",
+                self.column, self.reason
+            ),
+            None => write!(f, "cast error at column `{}`: {}", self.column, self.reason),
+        }
+    }
+}
+impl CastError {
+    pub fn new(column: &str, reason: &str) -> CastError {
+        CastError {
+            row: None,
+            column: column.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn at_row(self, row: usize) -> CastError {
+        CastError {
+            row: Some(row),
+            ..self
+        }
+    }
+}
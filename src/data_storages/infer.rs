@@ -0,0 +1,242 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+use super::{
+    data_storages::{
+        CoercionPolicy, Row, Schema, SchemaField, SchemaType, SchemaTypeWithValue, TimePrecision,
+    },
+    utils::{merge_schema, option_bool, option_parse},
+};
+
+// rows sampled to infer schema by default.
+const DEFAULT_SAMPLE_SIZE: usize = 100;
+
+/// inference of schema for sources without types, e.g. csv. It's enabled by `infer_schema=true`
+/// in options and samples the first `sample_size` rows.
+pub(super) struct InferOptions {
+    pub(super) enabled: bool,
+    pub(super) sample_size: usize,
+}
+
+impl InferOptions {
+    pub(super) fn from_options(options: &HashMap<&str, &str>) -> Result<Self> {
+        Ok(InferOptions {
+            enabled: option_bool(options, "infer_schema", false)?,
+            sample_size: option_parse(options, "sample_size", DEFAULT_SAMPLE_SIZE)?,
+        })
+    }
+}
+
+// timestamp type of text, with or without time zone.
+fn timestamp_type(s: &str) -> Option<SchemaType> {
+    let value = SchemaTypeWithValue::String(s.to_string());
+    value
+        .cast(&SchemaType::Timestamp(TimePrecision::Nano))
+        .or_else(|| value.cast(&SchemaType::Timestamptz(TimePrecision::Nano)))
+        .map(|value| value.schema_type())
+}
+
+/// narrowest type of text, in the order of bool, int32, int64, double, date and timestamp, or
+/// String if none of them.
+pub(super) fn infer_text_type(s: &str) -> SchemaType {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("true") || s.eq_ignore_ascii_case("false") {
+        SchemaType::Boolean
+    } else if let Ok(i) = s.parse::<i64>() {
+        if i32::try_from(i).is_ok() {
+            SchemaType::Int32
+        } else {
+            SchemaType::Int64
+        }
+    } else if s.parse::<f64>().is_ok() && s.contains(|c: char| c.is_ascii_digit()) {
+        SchemaType::Double
+    } else if NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok() {
+        SchemaType::Date
+    } else {
+        timestamp_type(s).unwrap_or(SchemaType::String)
+    }
+}
+
+/// narrowest type of value, text is inferred by `infer_text_type` if `text` is set, otherwise
+/// only dates and timestamps are taken from it.
+pub(super) fn infer_value_type(value: &SchemaTypeWithValue, text: bool) -> SchemaType {
+    match value {
+        SchemaTypeWithValue::String(s) if text => infer_text_type(s),
+        SchemaTypeWithValue::String(s) => match infer_text_type(s) {
+            type_ @ (SchemaType::Date | SchemaType::Timestamp(_) | SchemaType::Timestamptz(_)) => {
+                type_
+            }
+            _ => SchemaType::String,
+        },
+        SchemaTypeWithValue::Int64(i) if i32::try_from(*i).is_ok() => SchemaType::Int32,
+        value => value.schema_type(),
+    }
+}
+
+/// schema merged from narrowest types of sample rows, None if no rows. Columns which are null or
+/// missing in any row are nullable, columns of only nulls are String.
+pub(super) fn infer_schema(rows: &[Row], text: bool) -> Option<Schema> {
+    let schema = rows
+        .iter()
+        .map(|row| {
+            Schema(
+                row.0
+                    .iter()
+                    .map(|column| {
+                        let type_ = infer_value_type(&column.value, text);
+                        SchemaField {
+                            name: column.name.clone(),
+                            extra: type_.extra(),
+                            type_,
+                        }
+                    })
+                    .collect(),
+            )
+        })
+        .reduce(|merged, schema| merge_schema(&merged, &schema))?;
+    Some(Schema(
+        schema
+            .0
+            .into_iter()
+            .map(|mut field| {
+                let nullable = matches!(field.type_, SchemaType::None)
+                    || field.extra.get("nullable").map(String::as_str) == Some("true")
+                    || rows.iter().any(|row| row.get(&field.name).is_none());
+                if let SchemaType::None = field.type_ {
+                    field.type_ = SchemaType::String;
+                }
+                field
+                    .extra
+                    .insert("nullable".to_string(), nullable.to_string());
+                field
+            })
+            .collect(),
+    ))
+}
+
+/// rows cast to inferred `schema`, `offset` is the number of rows before them. Nullability is not
+/// checked since it comes from samples only.
+pub(super) fn apply_schema(rows: Vec<Row>, schema: &Schema, offset: usize) -> Result<Vec<Row>> {
+    let schema = Schema(
+        schema
+            .0
+            .iter()
+            .map(|field| {
+                let mut field = field.clone();
+                field.extra.remove("nullable");
+                field
+            })
+            .collect(),
+    );
+    rows.iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let (row, _) = row
+                .normalize(&schema, CoercionPolicy::Safe)
+                .map_err(|err| {
+                    anyhow::Error::from(err.at_row(offset + idx)).context(
+                        "value does not fit inferred schema, try larger `sample_size` or pin the schema",
+                    )
+                })?;
+            Ok(row)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::data_storages::Column;
+
+    #[test]
+    fn narrowest_text_type() {
+        for (text, expected) in [
+            ("true", SchemaType::Boolean),
+            (" FALSE ", SchemaType::Boolean),
+            ("12", SchemaType::Int32),
+            ("3000000000", SchemaType::Int64),
+            ("1.5", SchemaType::Double),
+            ("1e3", SchemaType::Double),
+            ("inf", SchemaType::String),
+            ("2024-01-02", SchemaType::Date),
+            (
+                "2024-01-02 03:04:05",
+                SchemaType::Timestamp(TimePrecision::Micro),
+            ),
+            (
+                "2024-01-02T03:04:05.123456789",
+                SchemaType::Timestamp(TimePrecision::Nano),
+            ),
+            (
+                "2024-01-02T03:04:05Z",
+                SchemaType::Timestamptz(TimePrecision::Micro),
+            ),
+            ("12abc", SchemaType::String),
+            ("", SchemaType::String),
+        ] {
+            assert_eq!(infer_text_type(text), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn infer_schema_of_samples() {
+        let row = |columns: Vec<(&str, SchemaTypeWithValue)>| {
+            Row(columns
+                .into_iter()
+                .map(|(name, value)| Column {
+                    name: name.to_string(),
+                    value,
+                })
+                .collect())
+        };
+        let text = |s: &str| SchemaTypeWithValue::String(s.to_string());
+        let rows = vec![
+            row(vec![
+                ("a", text("1")),
+                ("b", text("x")),
+                ("c", SchemaTypeWithValue::None),
+                ("d", text("2024-01-02")),
+            ]),
+            row(vec![
+                ("a", text("3000000000")),
+                ("b", text("2")),
+                ("d", text("1")),
+            ]),
+        ];
+        let fields = |schema: Schema| {
+            schema
+                .0
+                .into_iter()
+                .map(|field| (field.name, field.type_, field.extra["nullable"].clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            fields(infer_schema(&rows, true).unwrap()),
+            vec![
+                ("a".to_string(), SchemaType::Int64, "false".to_string()),
+                ("b".to_string(), SchemaType::String, "false".to_string()),
+                ("c".to_string(), SchemaType::String, "true".to_string()),
+                ("d".to_string(), SchemaType::String, "false".to_string()),
+            ]
+        );
+        // without `text` only dates and timestamps are taken from strings.
+        assert_eq!(
+            fields(infer_schema(&rows[..1], false).unwrap()),
+            vec![
+                ("a".to_string(), SchemaType::String, "false".to_string()),
+                ("b".to_string(), SchemaType::String, "false".to_string()),
+                ("c".to_string(), SchemaType::String, "true".to_string()),
+                ("d".to_string(), SchemaType::Date, "false".to_string()),
+            ]
+        );
+        assert!(infer_schema(&[], true).is_none());
+
+        let schema = infer_schema(&rows, true).unwrap();
+        let applied = apply_schema(rows[..1].to_vec(), &schema, 0).unwrap();
+        assert_eq!(applied[0].get("a"), Some(&SchemaTypeWithValue::Int64(1)));
+        let narrow = infer_schema(&rows[..1], true).unwrap();
+        let err = apply_schema(rows[1..].to_vec(), &narrow, 1).unwrap_err();
+        assert!(format!("{err:#}").contains("sample_size"), "{err:#}");
+    }
+}
@@ -0,0 +1,374 @@
+use super::{
+    data_storages::{from_json, BinaryEncoding, ReadResult, Row, Schema, SchemaTypeWithValue},
+    error::ParameterError,
+    infer::{apply_schema, infer_schema, InferOptions},
+    utils::{extract_file_uri_path, merge_schema, option_bool},
+    DataStorage,
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, BufWriter, Lines, Write},
+};
+
+#[derive(Debug)]
+pub struct JsonlDataStorage {
+    file: String,
+    // lines kept between `chunk_read` calls and the number of records they have consumed.
+    chunk_reader: Option<(Lines<BufReader<fs::File>>, i64)>,
+    // schema merged from the chunks read since the first chunk without inference, so columns of
+    // later chunks never disappear.
+    chunk_schema: Option<Schema>,
+    writer: Option<BufWriter<fs::File>>,
+    // schema inferred on first use if `infer_schema=true`, shared by later reads.
+    inferred: Option<Schema>,
+}
+
+impl JsonlDataStorage {
+    pub fn new(uri: &str) -> Self {
+        JsonlDataStorage {
+            file: extract_file_uri_path(uri, "file+jsonl"),
+            chunk_reader: None,
+            chunk_schema: None,
+            writer: None,
+            inferred: None,
+        }
+    }
+
+    fn lines(&self) -> Result<Lines<BufReader<fs::File>>> {
+        Ok(BufReader::new(fs::File::open(&self.file)?).lines())
+    }
+
+    // first `sample_size` rows.
+    fn sample(&self, sample_size: usize) -> Result<Vec<Row>> {
+        let mut lines = self.lines()?;
+        let mut sample: Vec<Row> = Vec::new();
+        while sample.len() < sample_size {
+            match next_row(&mut lines)? {
+                Some(row) => sample.push(row),
+                None => break,
+            }
+        }
+        Ok(sample)
+    }
+
+    // schema inferred from the first rows, None if inference is not enabled or file is empty.
+    fn inferred_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Option<Schema>> {
+        let infer_options = InferOptions::from_options(options)?;
+        if !infer_options.enabled {
+            return Ok(None);
+        }
+        if self.inferred.is_none() {
+            self.inferred = infer_schema(&self.sample(infer_options.sample_size)?, false);
+        }
+        Ok(self.inferred.clone())
+    }
+}
+
+/// object of line as row, nested arrays and objects are lists and structs.
+pub(super) fn line_to_row(line: &str) -> Result<Row> {
+    match from_json(&serde_json::from_str::<Value>(line)?) {
+        SchemaTypeWithValue::Struct(columns) => Ok(Row(columns)),
+        _ => Err(anyhow!("each line of jsonl must be an object, got {line}")),
+    }
+}
+
+pub(super) fn row_to_line(
+    row: &Row,
+    schema: &Option<Schema>,
+    binary_encoding: BinaryEncoding,
+) -> Result<String> {
+    let object = match schema {
+        Some(schema) => schema
+            .0
+            .iter()
+            .map(|field| {
+                let value = row.get(&field.name).unwrap_or(&SchemaTypeWithValue::None);
+                (field.name.clone(), value.encode_json(binary_encoding))
+            })
+            .collect::<Map<_, _>>(),
+        None => row
+            .0
+            .iter()
+            .map(|column| {
+                (
+                    column.name.clone(),
+                    column.value.encode_json(binary_encoding),
+                )
+            })
+            .collect::<Map<_, _>>(),
+    };
+    Ok(serde_json::to_string(&Value::Object(object))?)
+}
+
+// merged schema of rows, None if no rows.
+pub(super) fn rows_schema(rows: &[Row]) -> Option<Schema> {
+    rows.iter()
+        .map(Row::schema)
+        .reduce(|merged, schema| merge_schema(&merged, &schema))
+}
+
+// next non-empty line parsed as row.
+pub(super) fn next_row<B: BufRead>(lines: &mut Lines<B>) -> Result<Option<Row>> {
+    for line in lines.by_ref() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            return Ok(Some(line_to_row(&line)?));
+        }
+    }
+    Ok(None)
+}
+
+#[async_trait]
+impl DataStorage for JsonlDataStorage {
+    async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult> {
+        let mut lines = self.lines()?;
+        let mut data: Vec<Row> = Vec::new();
+        while let Some(row) = next_row(&mut lines)? {
+            data.push(row);
+        }
+        let (data, schema) = match self.inferred_schema(options)? {
+            Some(schema) => (apply_schema(data, &schema, 0)?, schema),
+            None => {
+                let schema = rows_schema(&data).unwrap_or(Schema(vec![]));
+                (data, schema)
+            }
+        };
+        Ok(ReadResult {
+            schema,
+            data,
+            cursor: None,
+        })
+    }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    /// write one object per row, the file will be truncated on the first write unless
+    /// `append=true`.
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        if self.writer.is_none() {
+            let append = option_bool(options, "append", false)?;
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(&self.file)?;
+            self.writer = Some(BufWriter::new(file));
+        }
+        let binary_encoding = BinaryEncoding::from_options(options)?;
+        let writer = self.writer.as_mut().unwrap();
+        for row in &data {
+            writer.write_all(row_to_line(row, &schema, binary_encoding)?.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// cursor is the number of records has been read.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let offset = match cursor {
+            None => 0,
+            Some(SchemaTypeWithValue::Int64(offset)) => offset,
+            Some(unk) => {
+                return Err(ParameterError::new(
+                    format!("invalid cursor {unk:?} for jsonl, must be a row offset").as_str(),
+                )
+                .into())
+            }
+        };
+        // reuse the reader if it stops at the cursor, otherwise reopen file and skip to it.
+        let (mut lines, mut consumed) = match self.chunk_reader.take() {
+            Some((lines, consumed)) if consumed == offset => (lines, consumed),
+            _ => (self.lines()?, 0),
+        };
+        while consumed < offset && next_row(&mut lines)?.is_some() {
+            consumed += 1;
+        }
+        let start = usize::try_from(consumed)?;
+        let mut data: Vec<Row> = Vec::new();
+        while data.len() < limit as usize {
+            match next_row(&mut lines)? {
+                Some(row) => {
+                    consumed += 1;
+                    data.push(row);
+                }
+                None => break,
+            }
+        }
+        let cursor = if data.is_empty() {
+            None
+        } else {
+            self.chunk_reader = Some((lines, consumed));
+            Some(SchemaTypeWithValue::Int64(consumed))
+        };
+        let (data, schema) = match self.inferred_schema(options)? {
+            Some(schema) => (apply_schema(data, &schema, start)?, schema),
+            None => {
+                let schema = match (
+                    self.chunk_schema.take().filter(|_| start > 0),
+                    rows_schema(&data),
+                ) {
+                    (Some(merged), Some(schema)) => merge_schema(&merged, &schema),
+                    (merged, schema) => merged.or(schema).unwrap_or(Schema(vec![])),
+                };
+                self.chunk_schema = Some(schema.clone());
+                (data, schema)
+            }
+        };
+        Ok(ReadResult {
+            schema,
+            data,
+            cursor,
+        })
+    }
+
+    /// schema merged from the first `sample_size` lines, with narrowest types and nullability if
+    /// `infer_schema=true`.
+    async fn read_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Schema> {
+        let schema = match self.inferred_schema(options)? {
+            Some(schema) => Some(schema),
+            None => rows_schema(&self.sample(InferOptions::from_options(options)?.sample_size)?),
+        };
+        schema.ok_or(ParameterError::new("cannot infer schema from empty file").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::data_storages::{Column, SchemaType};
+
+    fn column(name: &str, value: SchemaTypeWithValue) -> Column {
+        Column {
+            name: name.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn line_to_row_nests_lists_and_structs() {
+        let row = line_to_row(r#"{"id":1,"tags":["a"],"pt":{"x":1.5,"y":null}}"#).unwrap();
+        assert_eq!(
+            row,
+            Row(vec![
+                column("id", SchemaTypeWithValue::Int64(1)),
+                column(
+                    "tags",
+                    SchemaTypeWithValue::List(vec![SchemaTypeWithValue::String("a".to_string())])
+                ),
+                column(
+                    "pt",
+                    SchemaTypeWithValue::Struct(vec![
+                        column("x", SchemaTypeWithValue::Double(1.5)),
+                        column("y", SchemaTypeWithValue::None),
+                    ])
+                ),
+            ])
+        );
+        assert_eq!(
+            row.get("tags").unwrap().schema_type(),
+            SchemaType::List(Box::new(SchemaType::String))
+        );
+        assert!(line_to_row("[1]").is_err());
+    }
+
+    #[tokio::test]
+    async fn write_and_read_back() {
+        let rows = vec![
+            Row(vec![
+                column("id", SchemaTypeWithValue::Int64(1)),
+                column("name", SchemaTypeWithValue::String("a \"b\"\n".to_string())),
+                column("data", SchemaTypeWithValue::Binary(vec![0, 255])),
+                column(
+                    "tags",
+                    SchemaTypeWithValue::List(vec![SchemaTypeWithValue::Int64(1)]),
+                ),
+            ]),
+            Row(vec![
+                column("id", SchemaTypeWithValue::Int64(2)),
+                column("name", SchemaTypeWithValue::None),
+                column("data", SchemaTypeWithValue::None),
+                column("tags", SchemaTypeWithValue::List(vec![])),
+            ]),
+        ];
+        for (encoding, encoded) in [("hex", "00ff"), ("base64", "AP8=")] {
+            let path = std::env::temp_dir()
+                .join(format!("datawhirr-jsonl-{encoding}-{}", std::process::id()))
+                .to_string_lossy()
+                .to_string();
+            let options = HashMap::from([("binary_encoding", encoding)]);
+            let uri = format!("file+jsonl://{path}");
+            let mut storage = JsonlDataStorage::new(uri.as_str());
+            storage.write(rows.clone(), None, &options).await.unwrap();
+            let mut storage = JsonlDataStorage::new(uri.as_str());
+            let first = storage.chunk_read(None, 1, &options).await.unwrap();
+            let second = storage
+                .chunk_read(first.cursor.clone(), 1, &options)
+                .await
+                .unwrap();
+            fs::remove_file(&path).unwrap();
+            let mut expected = rows.clone();
+            expected[0].0[2].value = SchemaTypeWithValue::String(encoded.to_string());
+            assert_eq!(first.cursor, Some(SchemaTypeWithValue::Int64(1)));
+            assert_eq!(second.cursor, Some(SchemaTypeWithValue::Int64(2)));
+            assert_eq!([first.data, second.data].concat(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn chunk_schemas_are_merged() {
+        let path = std::env::temp_dir()
+            .join(format!("datawhirr-jsonl-chunks-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        fs::write(
+            &path,
+            "{\"b\":1,\"a\":1}\n{\"c\":\"x\",\"a\":2}\n{\"d\":true}\n",
+        )
+        .unwrap();
+        let mut storage = JsonlDataStorage::new(format!("file+jsonl://{path}").as_str());
+        let options = HashMap::new();
+        let mut cursor = None;
+        let mut schemas = vec![];
+        for _ in 0..3 {
+            let res = storage.chunk_read(cursor, 1, &options).await.unwrap();
+            cursor = res.cursor;
+            schemas.push(
+                res.schema
+                    .0
+                    .into_iter()
+                    .map(|field| field.name)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        // a new read starts from the schema of its first chunk.
+        let first = storage.chunk_read(None, 1, &options).await.unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            schemas,
+            vec![
+                vec!["b", "a"],
+                vec!["b", "a", "c"],
+                vec!["b", "a", "c", "d"]
+            ]
+        );
+        assert_eq!(first.schema.0.len(), 2);
+    }
+}
@@ -0,0 +1,468 @@
+use super::{
+    data_storages::{BinaryEncoding, Column, ReadResult, Row, Schema, SchemaTypeWithValue},
+    error::ParameterError,
+    jsonl::{line_to_row, row_to_line, rows_schema},
+    utils::{blocking, option_parse},
+    DataStorage,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use kafka::{
+    client::{FetchOffset, GroupOffsetStorage},
+    consumer::Consumer,
+    producer::{Producer, Record},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    str,
+};
+
+// messages sampled by `read_schema` by default.
+const DEFAULT_SAMPLE_SIZE: usize = 100;
+
+// row polled from topic, with the partition and offset of its message.
+type PolledRow = (i32, i64, Row);
+
+pub struct KafkaDataStorage {
+    brokers: Vec<String>,
+    topic: String,
+    // consumer kept between `chunk_read` calls until `commit`, the rows it has polled but not
+    // returned yet and the number of rows have been returned.
+    chunk_consumer: Option<(Consumer, VecDeque<PolledRow>, i64)>,
+    producer: Option<Producer>,
+}
+
+impl KafkaDataStorage {
+    /// uri in form of `kafka://broker1:9092[,broker2:9092]/topic`.
+    pub fn new(uri: &str) -> Result<Self> {
+        let (brokers, topic) = uri
+            .strip_prefix("kafka://")
+            .and_then(|rest| rest.split_once('/'))
+            .filter(|(brokers, topic)| !brokers.is_empty() && !topic.is_empty())
+            .ok_or(ParameterError::new(
+                format!("invalid kafka uri `{uri}`, must be `kafka://broker/topic`").as_str(),
+            ))?;
+        Ok(KafkaDataStorage {
+            brokers: brokers.split(',').map(String::from).collect(),
+            topic: topic.to_string(),
+            chunk_consumer: None,
+            producer: None,
+        })
+    }
+
+    async fn consumer(&self, options: &HashMap<&str, &str>) -> Result<Consumer> {
+        let mut builder = Consumer::from_hosts(self.brokers.clone())
+            .with_topic(self.topic.clone())
+            .with_fallback_offset(start_offset(options)?);
+        if let Some(group) = options.get("group") {
+            builder = builder
+                .with_group(group.to_string())
+                .with_offset_storage(Some(GroupOffsetStorage::Kafka));
+        }
+        blocking(move || Ok(builder.create()?)).await
+    }
+}
+
+// offset to start from if `group` has no committed offset, `earliest` by default.
+fn start_offset(options: &HashMap<&str, &str>) -> Result<FetchOffset> {
+    match options
+        .get("start_offset")
+        .map(|offset| offset.to_lowercase())
+        .as_deref()
+    {
+        None | Some("earliest") => Ok(FetchOffset::Earliest),
+        Some("latest") => Ok(FetchOffset::Latest),
+        Some(unk) => Err(ParameterError::new(
+            format!("`start_offset` must be `earliest` or `latest`, got `{unk}`").as_str(),
+        )
+        .into()),
+    }
+}
+
+// json payload as row, the message key is put into `key_column` if given.
+fn message_to_row(key: &[u8], value: &[u8], key_column: Option<&str>) -> Result<Row> {
+    let mut row = line_to_row(str::from_utf8(value)?)?;
+    if let Some(key_column) = key_column {
+        let key = if key.is_empty() {
+            SchemaTypeWithValue::None
+        } else {
+            SchemaTypeWithValue::String(str::from_utf8(key)?.to_string())
+        };
+        row.0.retain(|column| column.name != key_column);
+        row.0.insert(
+            0,
+            Column {
+                name: key_column.to_string(),
+                value: key,
+            },
+        );
+    }
+    Ok(row)
+}
+
+// key and json payload of message produced from row, the key is the text of `key_column`.
+fn row_to_message(
+    row: &Row,
+    schema: &Option<Schema>,
+    key_column: Option<&str>,
+    binary_encoding: BinaryEncoding,
+) -> Result<(Vec<u8>, String)> {
+    let key = key_column
+        .and_then(|key_column| row.get(key_column))
+        .and_then(|value| value.encode_text(binary_encoding))
+        .unwrap_or_default();
+    Ok((key.into_bytes(), row_to_line(row, schema, binary_encoding)?))
+}
+
+// poll messages into `pending` until it holds `want` rows or the topic has no more message.
+fn poll_rows(
+    consumer: &mut Consumer,
+    pending: &mut VecDeque<PolledRow>,
+    want: usize,
+    key_column: Option<&str>,
+) -> Result<()> {
+    while pending.len() < want {
+        let message_sets = consumer.poll()?;
+        if message_sets.is_empty() {
+            break;
+        }
+        for message_set in message_sets.iter() {
+            for message in message_set.messages() {
+                pending.push_back((
+                    message_set.partition(),
+                    message.offset,
+                    message_to_row(message.key, message.value, key_column)?,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// take at most `limit` rows from `pending`, they are marked as consumed and their offsets are
+// committed by `commit`.
+fn take_rows(
+    consumer: &mut Consumer,
+    pending: &mut VecDeque<PolledRow>,
+    limit: usize,
+    topic: &str,
+) -> Result<Vec<Row>> {
+    let mut data: Vec<Row> = Vec::new();
+    while data.len() < limit {
+        match pending.pop_front() {
+            Some((partition, offset, row)) => {
+                consumer.consume_message(topic, partition, offset)?;
+                data.push(row);
+            }
+            None => break,
+        }
+    }
+    Ok(data)
+}
+
+// poll and take at most `want` rows by the consumer on the blocking threads, the consumer and
+// rows polled but not taken are given back.
+async fn consume(
+    mut consumer: Consumer,
+    mut pending: VecDeque<PolledRow>,
+    want: usize,
+    options: &HashMap<&str, &str>,
+    topic: String,
+) -> Result<(Consumer, VecDeque<PolledRow>, Vec<Row>)> {
+    let key_column = options.get("key_column").map(|column| column.to_string());
+    blocking(move || {
+        poll_rows(&mut consumer, &mut pending, want, key_column.as_deref())?;
+        let data = take_rows(&mut consumer, &mut pending, want, &topic)?;
+        Ok((consumer, pending, data))
+    })
+    .await
+}
+
+#[async_trait]
+impl DataStorage for KafkaDataStorage {
+    /// schema merged from the first `sample_size` messages, offsets are not committed.
+    async fn read_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Schema> {
+        let sample_size = option_parse(options, "sample_size", DEFAULT_SAMPLE_SIZE)?;
+        let mut consumer = self.consumer(options).await?;
+        let key_column = options.get("key_column").map(|column| column.to_string());
+        let pending = blocking(move || {
+            let mut pending: VecDeque<PolledRow> = VecDeque::new();
+            poll_rows(
+                &mut consumer,
+                &mut pending,
+                sample_size,
+                key_column.as_deref(),
+            )?;
+            Ok(pending)
+        })
+        .await?;
+        let sample = pending
+            .into_iter()
+            .take(sample_size)
+            .map(|(_, _, row)| row)
+            .collect::<Vec<_>>();
+        rows_schema(&sample)
+            .ok_or(ParameterError::new("cannot infer schema from empty topic").into())
+    }
+
+    /// consume messages until the topic has no more message or `max_messages` is reached. Offsets
+    /// of `group` are committed by `commit` after rows are written, messages are delivered at
+    /// least once.
+    async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult> {
+        let max_messages = option_parse(options, "max_messages", usize::MAX)?;
+        let consumer = self.consumer(options).await?;
+        let (consumer, pending, data) = consume(
+            consumer,
+            VecDeque::new(),
+            max_messages,
+            options,
+            self.topic.clone(),
+        )
+        .await?;
+        let returned = i64::try_from(data.len())?;
+        self.chunk_consumer = Some((consumer, pending, returned));
+        Ok(ReadResult {
+            schema: rows_schema(&data).unwrap_or(Schema(vec![])),
+            data,
+            cursor: None,
+        })
+    }
+
+    /// cursor is the number of messages has been read, kafka can not seek to it so only the
+    /// cursor returned by last call is accepted.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let max_messages = option_parse(options, "max_messages", i64::MAX)?;
+        let (consumer, pending, returned) = match (cursor, self.chunk_consumer.take()) {
+            (None, _) => (self.consumer(options).await?, VecDeque::new(), 0),
+            (Some(SchemaTypeWithValue::Int64(offset)), Some((consumer, pending, returned)))
+                if offset == returned =>
+            {
+                (consumer, pending, returned)
+            }
+            (Some(unk), _) => {
+                return Err(ParameterError::new(
+                    format!("invalid cursor {unk:?} for kafka, must be returned by last read")
+                        .as_str(),
+                )
+                .into())
+            }
+        };
+        let want = usize::try_from((max_messages - returned).clamp(0, i64::from(limit)))?;
+        let (consumer, pending, data) =
+            consume(consumer, pending, want, options, self.topic.clone()).await?;
+        let returned = returned + i64::try_from(data.len())?;
+        let cursor = (!data.is_empty()).then_some(SchemaTypeWithValue::Int64(returned));
+        // the consumer is kept to commit its offsets even if the topic has no more message.
+        self.chunk_consumer = Some((consumer, pending, returned));
+        Ok(ReadResult {
+            schema: rows_schema(&data).unwrap_or(Schema(vec![])),
+            data,
+            cursor,
+        })
+    }
+
+    /// commit offsets of messages read by `group`, nothing without group.
+    async fn commit(&mut self) -> Result<()> {
+        if let Some((mut consumer, pending, returned)) = self.chunk_consumer.take() {
+            let consumer = blocking(move || {
+                if !consumer.group().is_empty() {
+                    consumer.commit_consumed()?;
+                }
+                Ok(consumer)
+            })
+            .await?;
+            self.chunk_consumer = Some((consumer, pending, returned));
+        }
+        Ok(())
+    }
+
+    fn concurrent_write(&self) -> bool {
+        true
+    }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    /// produce one json message per row, keyed by the text of `key_column` if given.
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        let key_column = options.get("key_column").copied();
+        let binary_encoding = BinaryEncoding::from_options(options)?;
+        let messages = data
+            .iter()
+            .map(|row| row_to_message(row, &schema, key_column, binary_encoding))
+            .collect::<Result<Vec<_>>>()?;
+        let (producer, brokers, topic) = (
+            self.producer.take(),
+            self.brokers.clone(),
+            self.topic.clone(),
+        );
+        self.producer = Some(
+            blocking(move || {
+                let mut producer = match producer {
+                    Some(producer) => producer,
+                    None => Producer::from_hosts(brokers).create()?,
+                };
+                let records = messages
+                    .iter()
+                    .map(|(key, value)| {
+                        Record::from_key_value(&topic, key.as_slice(), value.as_str())
+                    })
+                    .collect::<Vec<_>>();
+                producer.send_all(&records)?;
+                Ok(producer)
+            })
+            .await?,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kafka::client::KafkaClient;
+    use std::{thread, time::Duration};
+
+    // brokers of the test kafka, which is started by `test_control.sh kafka`.
+    fn brokers() -> String {
+        std::env::var("KAFKA_BROKERS").unwrap_or("localhost:9092".to_string())
+    }
+
+    // topic of the test, brokers create it on the first request of its metadata.
+    fn create_topic(name: &str) -> String {
+        let topic = format!("datawhirr-{name}-{}", std::process::id());
+        let mut client = KafkaClient::new(brokers().split(',').map(String::from).collect());
+        for _ in 0..10 {
+            if client.load_metadata(&[&topic]).is_ok()
+                && client
+                    .topics()
+                    .partitions(&topic)
+                    .is_some_and(|partitions| !partitions.is_empty())
+            {
+                return topic;
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+        panic!("cannot create topic `{topic}`");
+    }
+
+    // ids of all messages read by `group`, offsets are committed if `commit`.
+    async fn read_ids(topic: &str, group: &str, commit: bool) -> Vec<i64> {
+        let mut storage = KafkaDataStorage::new(&format!("kafka://{}/{topic}", brokers())).unwrap();
+        let options = HashMap::from([("group", group)]);
+        let mut ids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let res = storage.chunk_read(cursor, 2, &options).await.unwrap();
+            ids.extend(res.data.iter().map(|row| match row.get("id") {
+                Some(SchemaTypeWithValue::Int64(id)) => *id,
+                other => unreachable!("unexpected id {other:?}"),
+            }));
+            cursor = res.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        if commit {
+            storage.commit().await.unwrap();
+        }
+        ids
+    }
+
+    #[test]
+    fn start_offset_of_options() {
+        assert!(matches!(
+            start_offset(&HashMap::new()),
+            Ok(FetchOffset::Earliest)
+        ));
+        assert!(matches!(
+            start_offset(&HashMap::from([("start_offset", "Latest")])),
+            Ok(FetchOffset::Latest)
+        ));
+        assert!(start_offset(&HashMap::from([("start_offset", "0")])).is_err());
+    }
+
+    #[test]
+    fn message_row_round_trip() {
+        let row = Row(vec![
+            Column {
+                name: "id".to_string(),
+                value: SchemaTypeWithValue::Int64(7),
+            },
+            Column {
+                name: "payload".to_string(),
+                value: SchemaTypeWithValue::Binary(vec![0, 255]),
+            },
+        ]);
+        let (key, value) = row_to_message(&row, &None, Some("id"), BinaryEncoding::Hex).unwrap();
+        assert_eq!(key, b"7");
+        assert_eq!(value, r#"{"id":7,"payload":"00ff"}"#);
+        // the key replaces the column of the same name, as text.
+        assert_eq!(
+            message_to_row(&key, value.as_bytes(), Some("id")).unwrap(),
+            Row(vec![
+                Column {
+                    name: "id".to_string(),
+                    value: SchemaTypeWithValue::String("7".to_string()),
+                },
+                Column {
+                    name: "payload".to_string(),
+                    value: SchemaTypeWithValue::String("00ff".to_string()),
+                },
+            ])
+        );
+        assert_eq!(
+            message_to_row(&key, value.as_bytes(), None)
+                .unwrap()
+                .get("id"),
+            Some(&SchemaTypeWithValue::Int64(7))
+        );
+
+        // messages without key have null keys, rows without key column have empty keys.
+        let keyless = message_to_row(b"", br#"{"id":1}"#, Some("key")).unwrap();
+        assert_eq!(keyless.get("key"), Some(&SchemaTypeWithValue::None));
+        assert_eq!(keyless.get("id"), Some(&SchemaTypeWithValue::Int64(1)));
+        let (key, _) = row_to_message(&keyless, &None, Some("key"), BinaryEncoding::Hex).unwrap();
+        assert!(key.is_empty());
+        assert!(message_to_row(b"", b"[1]", None).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires kafka, start it by `test_control.sh kafka`"]
+    async fn commits_offsets_after_commit() {
+        let topic = create_topic("commit");
+        let mut sink = KafkaDataStorage::new(&format!("kafka://{}/{topic}", brokers())).unwrap();
+        let rows = (0..5)
+            .map(|id| {
+                Row(vec![Column {
+                    name: "id".to_string(),
+                    value: SchemaTypeWithValue::Int64(id),
+                }])
+            })
+            .collect();
+        sink.write(rows, None, &HashMap::new()).await.unwrap();
+
+        let group = topic.as_str();
+        assert_eq!(
+            read_ids(&topic, group, false).await,
+            (0..5).collect::<Vec<_>>()
+        );
+        // messages are read again until they are committed.
+        assert_eq!(
+            read_ids(&topic, group, true).await,
+            (0..5).collect::<Vec<_>>()
+        );
+        assert!(read_ids(&topic, group, false).await.is_empty());
+    }
+}
@@ -1,11 +1,16 @@
-use super::{csv::CSVDataStorage, data_storages::DataStorage, pgsql::PgSqlStorage};
+use super::{
+    arrow_ipc::ArrowDataStorage, csv::CSVDataStorage, data_storages::DataStorage,
+    jsonl::JsonlDataStorage, kafka::KafkaDataStorage, mysql::MySqlStorage,
+    parquet::ParquetDataStorage, pgsql::PgSqlStorage, sqlite::SqliteStorage,
+    stdio::StdioDataStorage,
+};
 use core::panic;
 use fluent_uri::Uri;
 use std::collections::HashMap;
 
 pub async fn load_data_storage(
     storage_uri: &str,
-    options: &HashMap<String, String>,
+    _options: &HashMap<String, String>,
 ) -> Box<dyn DataStorage + Send> {
     match Uri::parse(storage_uri)
         .expect("cannot parse uri")
@@ -17,7 +22,24 @@ pub async fn load_data_storage(
         "postgres" => {
             Box::new(PgSqlStorage::new(storage_uri).await.unwrap()) as Box<dyn DataStorage + Send>
         }
+        "mysql" | "mariadb" => {
+            Box::new(MySqlStorage::new(storage_uri).await.unwrap()) as Box<dyn DataStorage + Send>
+        }
+        "sqlite" => {
+            Box::new(SqliteStorage::new(storage_uri).await.unwrap()) as Box<dyn DataStorage + Send>
+        }
         "file+csv" => Box::new(CSVDataStorage::new(storage_uri)) as Box<dyn DataStorage + Send>,
+        "file+jsonl" => Box::new(JsonlDataStorage::new(storage_uri)) as Box<dyn DataStorage + Send>,
+        "file+parquet" => {
+            Box::new(ParquetDataStorage::new(storage_uri)) as Box<dyn DataStorage + Send>
+        }
+        "stdio" | "stdio+csv" | "stdio+jsonl" => {
+            Box::new(StdioDataStorage::new(storage_uri).unwrap()) as Box<dyn DataStorage + Send>
+        }
+        "file+arrow" => Box::new(ArrowDataStorage::new(storage_uri)) as Box<dyn DataStorage + Send>,
+        "kafka" => {
+            Box::new(KafkaDataStorage::new(storage_uri).unwrap()) as Box<dyn DataStorage + Send>
+        }
         _ => panic!("not supported this type of uri yet"),
     }
 }
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{
+    data_storages::{DataStorage, ReadResult, Row, Schema, SchemaTypeWithValue},
+    error::ParameterError,
+    utils::option_parse,
+};
+
+/// storage which reads rows from and writes rows into memory, writes of every clone are shared.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    rows: Vec<Row>,
+    written: Arc<Mutex<Vec<Row>>>,
+    closed: Arc<Mutex<usize>>,
+    committed: Arc<Mutex<usize>>,
+    // reads which start from this offset fail.
+    pub fail_read_at: Option<usize>,
+    pub fail_write: bool,
+}
+
+impl MemoryStorage {
+    pub fn new(rows: Vec<Row>) -> Self {
+        MemoryStorage {
+            rows,
+            ..Default::default()
+        }
+    }
+
+    pub fn written(&self) -> Vec<Row> {
+        self.written.lock().unwrap().clone()
+    }
+
+    /// number of `close` calls of all clones.
+    pub fn closed(&self) -> usize {
+        *self.closed.lock().unwrap()
+    }
+
+    /// number of `commit` calls of all clones.
+    pub fn committed(&self) -> usize {
+        *self.committed.lock().unwrap()
+    }
+}
+
+#[async_trait]
+impl DataStorage for MemoryStorage {
+    async fn read_schema(&mut self, _: &HashMap<&str, &str>) -> Result<Schema> {
+        Ok(self.rows.first().map_or(Schema(vec![]), |row| row.schema()))
+    }
+
+    async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult> {
+        self.chunk_read(None, u32::MAX, options).await
+    }
+
+    /// cursor is the offset of rows has been read, rows are read from `start` to `end` in options.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let offset = match cursor {
+            None => option_parse(options, "start", 0)?,
+            Some(SchemaTypeWithValue::Int64(offset)) => usize::try_from(offset)?,
+            Some(unk) => {
+                return Err(ParameterError::new(
+                    format!("invalid cursor {unk:?} for memory, must be a row offset").as_str(),
+                )
+                .into())
+            }
+        };
+        if self.fail_read_at.is_some_and(|fail_at| offset >= fail_at) {
+            return Err(anyhow!("read failed at {offset}"));
+        }
+        let end = self
+            .rows
+            .len()
+            .min(option_parse(options, "end", usize::MAX)?)
+            .min(offset.saturating_add(limit as usize));
+        let data = self.rows[offset.min(end)..end].to_vec();
+        let cursor = (!data.is_empty()).then_some(SchemaTypeWithValue::Int64(i64::try_from(end)?));
+        Ok(ReadResult {
+            schema: self.read_schema(options).await?,
+            data,
+            cursor,
+        })
+    }
+
+    /// split rows into `partitions` ranges of offsets.
+    async fn partition(
+        &mut self,
+        options: &HashMap<&str, &str>,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        let partitions = option_parse(options, "partitions", 1usize)?.max(1);
+        let size = self.rows.len().div_ceil(partitions).max(1);
+        Ok((0..partitions)
+            .map(|idx| {
+                HashMap::from([
+                    ("start".to_string(), (idx * size).to_string()),
+                    ("end".to_string(), ((idx + 1) * size).to_string()),
+                ])
+            })
+            .collect())
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        *self.committed.lock().unwrap() += 1;
+        Ok(())
+    }
+
+    fn concurrent_write(&self) -> bool {
+        true
+    }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        _: Option<Schema>,
+        _: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        if self.fail_write {
+            return Err(anyhow!("write failed"));
+        }
+        self.written.lock().unwrap().extend(data);
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        *self.closed.lock().unwrap() += 1;
+        Ok(())
+    }
+}
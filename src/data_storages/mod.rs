@@ -1,6 +1,22 @@
+mod arrow_convert;
+pub mod arrow_ipc;
 pub mod csv;
+#[allow(clippy::module_inception)]
 pub mod data_storages;
+pub mod error;
+mod infer;
+pub mod jsonl;
+pub mod kafka;
 pub mod loader;
+#[cfg(test)]
+pub mod memory;
 pub use data_storages::DataStorage;
+mod mysql;
+#[allow(dead_code)]
 mod none;
+pub mod parquet;
 mod pgsql;
+pub mod shared;
+mod sqlite;
+pub mod stdio;
+mod utils;
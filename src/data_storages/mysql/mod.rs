@@ -0,0 +1,3 @@
+mod my;
+mod parser;
+pub use my::MySqlStorage;
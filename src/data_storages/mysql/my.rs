@@ -0,0 +1,381 @@
+use crate::data_storages::{
+    data_storages::{self, ReadResult, SchemaTypeWithValue},
+    error::ParameterError,
+    mysql::parser::{parse_col_to_typed_value, parse_row_schema, ColumnSchemaInDB, UuidTypes},
+    utils::{
+        incremental_query, merge_schema, option_parse, partition_queries, query_from_options,
+        split_range, valid_symbol, valid_table,
+    },
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use sqlx::{
+    error::Error as SqlXError,
+    mysql::{MySql, MySqlArguments, MySqlConnection, MySqlRow},
+    query::Query,
+    Column, Connection, Row, TypeInfo,
+};
+
+// rows inserted by one `insert` statement by default.
+const DEFAULT_BATCH_SIZE: usize = 1000;
+// mysql limits the number of placeholders in a prepared statement.
+const MAX_PLACEHOLDERS: usize = 65535;
+
+pub struct MySqlStorage {
+    connection: MySqlConnection,
+}
+
+impl MySqlStorage {
+    pub async fn new(uri: &str) -> Result<Self, SqlXError> {
+        Ok(MySqlStorage {
+            connection: MySqlConnection::connect(uri).await?,
+        })
+    }
+
+    async fn fetch_rows(
+        &mut self,
+        sql: &str,
+        params: Vec<SchemaTypeWithValue>,
+        uuid_types: UuidTypes,
+    ) -> Result<(Vec<data_storages::Row>, Option<data_storages::Schema>)> {
+        let query = params.into_iter().fold(sqlx::query(sql), |query, value| {
+            bind_value(query, value, uuid_types)
+        });
+        let mut rows = query.fetch(&mut self.connection);
+        let mut results: Vec<data_storages::Row> = Vec::new();
+        let mut schema: Option<data_storages::Schema> = None;
+        while let Some(row) = rows.try_next().await? {
+            if let Some(s) = schema {
+                schema = Some(merge_schema(&s, &parse_row_schema(&row, uuid_types)?));
+            } else {
+                schema = Some(parse_row_schema(&row, uuid_types)?);
+            };
+            results.push(mysqlrow_to_row(row, uuid_types)?)
+        }
+        Ok((results, schema))
+    }
+
+    async fn primary_key(&mut self, table: &str) -> Result<String> {
+        let sql = "
+            SELECT cast(column_name as char) as column_name
+            FROM information_schema.key_column_usage
+            WHERE table_schema = database() AND table_name = ? AND constraint_name = 'PRIMARY'
+            ORDER BY ordinal_position";
+        let mut pks = sqlx::query(sql)
+            .bind(table)
+            .fetch_all(&mut self.connection)
+            .await?
+            .into_iter()
+            .map(|row| row.get::<String, _>("column_name"))
+            .collect::<Vec<_>>();
+        match pks.len() {
+            1 => Ok(pks.swap_remove(0)),
+            0 => Err(ParameterError::new(
+                "cannot find primary key of table, please specific `pk` in options",
+            )
+            .into()),
+            _ => Err(ParameterError::new(
+                "composite primary key is not supported, please specific `pk` in options",
+            )
+            .into()),
+        }
+    }
+
+    // column which chunks are paged by, `pk` in options or primary key of `table`.
+    async fn chunk_pk(
+        &mut self,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<String> {
+        match (options.get("pk"), options.get("table")) {
+            (Some(pk), _) => Ok(pk.to_string()),
+            (None, Some(table)) => self.primary_key(table).await,
+            (None, None) => {
+                Err(ParameterError::new("cannot find required options `pk` on chunk_read").into())
+            }
+        }
+    }
+}
+
+// uuid is bound as 16 bytes if `binary(16)` is in `uuid_types`, otherwise as text.
+fn bind_value(
+    query: Query<'_, MySql, MySqlArguments>,
+    value: SchemaTypeWithValue,
+    uuid_types: UuidTypes,
+) -> Query<'_, MySql, MySqlArguments> {
+    match value {
+        SchemaTypeWithValue::String(v) => query.bind(v),
+        SchemaTypeWithValue::Int32(v) => query.bind(v),
+        SchemaTypeWithValue::Int64(v) => query.bind(v),
+        SchemaTypeWithValue::Binary(v) => query.bind(v),
+        SchemaTypeWithValue::Boolean(v) => query.bind(v),
+        SchemaTypeWithValue::Timestamp(v) => query.bind(v),
+        SchemaTypeWithValue::Timestamptz(v) => query.bind(v),
+        SchemaTypeWithValue::Date(v) => query.bind(v),
+        SchemaTypeWithValue::Time(v) => query.bind(v),
+        // mysql has no interval type.
+        SchemaTypeWithValue::Interval(v) => query.bind(v.to_iso8601()),
+        SchemaTypeWithValue::Double(v) => query.bind(v),
+        SchemaTypeWithValue::Float(v) => query.bind(v),
+        SchemaTypeWithValue::Decimal(v) => query.bind(v),
+        SchemaTypeWithValue::Uuid(v) if uuid_types.binary => query.bind(v.as_bytes().to_vec()),
+        SchemaTypeWithValue::Uuid(v) => query.bind(v.hyphenated().to_string()),
+        SchemaTypeWithValue::Json(v) => query.bind(v),
+        // mysql has no nested types, they are kept as json.
+        v @ (SchemaTypeWithValue::List(_) | SchemaTypeWithValue::Struct(_)) => {
+            query.bind(v.to_json())
+        }
+        SchemaTypeWithValue::None => query.bind(Option::<String>::None),
+    }
+}
+
+// rows after the watermark bound as the parameter.
+fn incremental_condition(column: &str, _: &SchemaTypeWithValue) -> String {
+    format!("`{column}` > ?")
+}
+
+fn mysqlrow_to_row(row: MySqlRow, uuid_types: UuidTypes) -> Result<data_storages::Row> {
+    Ok(data_storages::Row(
+        row.columns()
+            .iter()
+            .map(|column| {
+                let column_name = column.name();
+                Ok(data_storages::Column {
+                    name: column_name.to_string(),
+                    value: parse_col_to_typed_value(
+                        column.type_info().name(),
+                        column_name,
+                        &row,
+                        uuid_types,
+                    )?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+#[async_trait]
+impl data_storages::DataStorage for MySqlStorage {
+    async fn read_schema(
+        &mut self,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<data_storages::Schema> {
+        if let Some(table) = options.get("table") {
+            let uuid_types = UuidTypes::from_options(options)?;
+            // information_schema columns may be binary strings on some versions, cast them.
+            let sql = "
+            SELECT cast(column_name as char) as column_name,
+                   cast(data_type as char) as data_type,
+                   cast(column_type as char) as column_type,
+                   cast(is_nullable as char) as is_nullable,
+                   cast(character_maximum_length as signed) as character_maximum_length,
+                   cast(numeric_precision as signed) as numeric_precision,
+                   cast(numeric_scale as signed) as numeric_scale
+            FROM information_schema.columns
+            WHERE table_schema = database() AND table_name = ?
+            ORDER BY ordinal_position";
+            let mut rows = sqlx::query(sql).bind(table).fetch(&mut self.connection);
+            let mut results: Vec<data_storages::SchemaField> = Vec::new();
+            while let Some(row) = rows.try_next().await? {
+                // columns of unsupported types are skipped, reading them fails by the type.
+                if let Ok(field) = ColumnSchemaInDB::from(row).to_data_schema(uuid_types) {
+                    results.push(field);
+                }
+            }
+            Ok(data_storages::Schema(results))
+        } else {
+            Err(ParameterError::new("cannot find `table` in options").into())
+        }
+    }
+
+    async fn read(
+        &mut self,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let (query, watermark) = incremental_query(options, incremental_condition)?;
+        let (data, schema) = self
+            .fetch_rows(
+                query.as_str(),
+                watermark.into_iter().collect(),
+                UuidTypes::from_options(options)?,
+            )
+            .await?;
+        Ok(ReadResult {
+            data,
+            schema: schema.unwrap_or(data_storages::Schema(vec![])),
+            cursor: None,
+        })
+    }
+
+    /// page by primary key(or `pk` in options), the cursor is the `pk` value of last row.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let (query, watermark) = incremental_query(options, incremental_condition)?;
+        let pk = self.chunk_pk(options).await?;
+        valid_symbol(pk.as_str())?;
+        let condition = match cursor {
+            Some(_) => format!("where `{pk}` > ?"),
+            None => String::new(),
+        };
+        let sql = format!(
+            "select * from ({query}) as datawhirr_chunk {condition} order by `{pk}` asc limit {limit}"
+        );
+        // the watermark is in the inner query, before the cursor.
+        let params = watermark.into_iter().chain(cursor).collect();
+        let (data, schema) = self
+            .fetch_rows(sql.as_str(), params, UuidTypes::from_options(options)?)
+            .await?;
+        let cursor = data
+            .last()
+            .map(|row| {
+                row.get(pk.as_str()).cloned().ok_or(ParameterError::new(
+                    "cannot find `pk` column in the result of query",
+                ))
+            })
+            .transpose()?;
+        Ok(ReadResult {
+            data,
+            schema: schema.unwrap_or(data_storages::Schema(vec![])),
+            cursor,
+        })
+    }
+
+    /// split `partition_column`(`pk` by default) into `partitions` ranges of equal width between
+    /// its min and max, each part reads its range by `query`.
+    async fn partition(
+        &mut self,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<Vec<std::collections::HashMap<String, String>>> {
+        let partitions: u32 = option_parse(options, "partitions", 1)?;
+        let mut part = options
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<std::collections::HashMap<_, _>>();
+        if partitions <= 1 {
+            return Ok(vec![part]);
+        }
+        let (query, watermark) = incremental_query(options, incremental_condition)?;
+        let pk = self.chunk_pk(options).await?;
+        let column = options
+            .get("partition_column")
+            .map_or(pk.clone(), |column| column.trim().to_string());
+        valid_symbol(column.as_str())?;
+        let quoted = format!("`{column}`");
+        let sql = format!(
+            "select min({quoted}) as min, max({quoted}) as max from ({query}) as datawhirr_partition"
+        );
+        let uuid_types = UuidTypes::from_options(options)?;
+        let row = watermark
+            .into_iter()
+            .fold(sqlx::query(sql.as_str()), |query, value| {
+                bind_value(query, value, uuid_types)
+            })
+            .fetch_one(&mut self.connection)
+            .await?;
+        let type_str = row.column(0).type_info().name().to_string();
+        let min = parse_col_to_typed_value(type_str.as_str(), "min", &row, uuid_types)?;
+        let max =
+            parse_col_to_typed_value(row.column(1).type_info().name(), "max", &row, uuid_types)?;
+        // the session of sqlx is in UTC, where timestamps are compared without offset.
+        let bounds = split_range(&min, &max, partitions)
+            .ok_or(ParameterError::new(
+                format!("cannot partition by `{column}` of type {type_str}").as_str(),
+            ))?
+            .into_iter()
+            .map(|bound| match bound {
+                SchemaTypeWithValue::Timestamptz(t) => {
+                    SchemaTypeWithValue::Timestamp(t.naive_utc())
+                }
+                bound => bound,
+            })
+            .collect::<Vec<_>>();
+        part.remove("table");
+        part.insert("pk".to_string(), pk);
+        // parts are filtered by the watermark again when they are read.
+        let query = query_from_options(options)?;
+        Ok(partition_queries(query.as_str(), quoted.as_str(), &bounds)?
+            .into_iter()
+            .map(|query| {
+                let mut part = part.clone();
+                part.insert("query".to_string(), query);
+                part
+            })
+            .collect())
+    }
+
+    fn incremental(&self) -> bool {
+        true
+    }
+
+    fn concurrent_write(&self) -> bool {
+        true
+    }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    /// insert rows with batched multi-row `insert` statements in one transaction.
+    async fn write(
+        &mut self,
+        data: Vec<data_storages::Row>,
+        schema: Option<data_storages::Schema>,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<()> {
+        let table = options
+            .get("table")
+            .ok_or(ParameterError::new("cannot find `table` in options"))?;
+        valid_table(table)?;
+        let schema = match (schema, data.first()) {
+            (_, None) => return Ok(()),
+            (Some(schema), _) => schema,
+            (None, Some(row)) => row.schema(),
+        };
+        if schema.0.is_empty() {
+            return Err(ParameterError::new("cannot write rows without any column").into());
+        }
+        for field in &schema.0 {
+            valid_symbol(field.name.as_str())?;
+        }
+        let batch_size = options
+            .get("batch_size")
+            .map(|size| size.parse::<usize>())
+            .transpose()?
+            .unwrap_or(DEFAULT_BATCH_SIZE)
+            .clamp(1, MAX_PLACEHOLDERS / schema.0.len());
+
+        let columns = schema
+            .0
+            .iter()
+            .map(|field| format!("`{}`", field.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let placeholders = format!("({})", vec!["?"; schema.0.len()].join(", "));
+        let uuid_types = UuidTypes::from_options(options)?;
+        let mut tx = self.connection.begin().await?;
+        for batch in data.chunks(batch_size) {
+            let sql = format!(
+                "insert into {table} ({columns}) values {}",
+                vec![placeholders.as_str(); batch.len()].join(", ")
+            );
+            let mut query = sqlx::query(sql.as_str());
+            for row in batch {
+                for field in &schema.0 {
+                    let value = row
+                        .get(&field.name)
+                        .cloned()
+                        .unwrap_or(SchemaTypeWithValue::None);
+                    query = bind_value(query, value, uuid_types);
+                }
+            }
+            query.execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::data_storages::{
+    data_storages::{self, TimePrecision},
+    error::ParameterError,
+    utils,
+};
+use sqlx::mysql::{MySql, MySqlRow};
+use sqlx::{Column, Decode, Row, Type, TypeInfo};
+
+fn get_value<'r, T>(
+    row: &'r MySqlRow,
+    column_name: &str,
+    to_value: impl FnOnce(T) -> Result<data_storages::SchemaTypeWithValue>,
+) -> Result<data_storages::SchemaTypeWithValue>
+where
+    T: Decode<'r, MySql> + Type<MySql>,
+{
+    match row.try_get::<Option<T>, _>(column_name)? {
+        Some(value) => to_value(value),
+        None => Ok(data_storages::SchemaTypeWithValue::None),
+    }
+}
+
+/// like `get_value` without checking the type of column, for types which sqlx decodes but does
+/// not declare compatible, e.g. `year` or `bit` as unsigned integer or bytes.
+fn get_unchecked_value<'r, T>(
+    row: &'r MySqlRow,
+    column_name: &str,
+    to_value: impl FnOnce(T) -> Result<data_storages::SchemaTypeWithValue>,
+) -> Result<data_storages::SchemaTypeWithValue>
+where
+    T: Decode<'r, MySql> + Type<MySql>,
+{
+    match row.try_get_unchecked::<Option<T>, _>(column_name)? {
+        Some(value) => to_value(value),
+        None => Ok(data_storages::SchemaTypeWithValue::None),
+    }
+}
+
+/// mysql has no uuid type, columns of types in `uuid_types` option are taken as uuid, which is
+/// `binary(16)` by default and could be `binary(16)`, `char(36)` or both separated by comma.
+#[derive(Debug, Clone, Copy)]
+pub struct UuidTypes {
+    pub binary: bool,
+    pub char: bool,
+}
+
+impl UuidTypes {
+    pub fn from_options(options: &HashMap<&str, &str>) -> Result<Self> {
+        let mut uuid_types = UuidTypes {
+            binary: false,
+            char: false,
+        };
+        for type_ in options
+            .get("uuid_types")
+            .unwrap_or(&"binary(16)")
+            .split(',')
+            .map(str::trim)
+            .filter(|type_| !type_.is_empty())
+        {
+            match type_.to_lowercase().as_str() {
+                "binary(16)" => uuid_types.binary = true,
+                "char(36)" => uuid_types.char = true,
+                unk => {
+                    return Err(ParameterError::new(
+                        format!("`uuid_types` must be `binary(16)` or `char(36)`, got `{unk}`")
+                            .as_str(),
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(uuid_types)
+    }
+
+    // whether values of row typed `type_name` may be uuid, the length is only known by value.
+    fn may_contain(&self, type_name: &str) -> bool {
+        match type_name {
+            "BINARY" => self.binary,
+            "CHAR" => self.char,
+            _ => false,
+        }
+    }
+}
+
+pub fn parse_col_to_typed_value(
+    type_name: &str,
+    column_name: &str,
+    row: &MySqlRow,
+    uuid_types: UuidTypes,
+) -> Result<data_storages::SchemaTypeWithValue> {
+    use data_storages::SchemaTypeWithValue as V;
+    match type_name {
+        "BINARY" if uuid_types.binary => get_value(row, column_name, |v: Vec<u8>| {
+            Ok(match Uuid::from_slice(&v) {
+                Ok(u) => V::Uuid(u),
+                Err(_) => V::Binary(v),
+            })
+        }),
+        "CHAR" if uuid_types.char => get_value(row, column_name, |v: String| {
+            Ok(match Uuid::try_parse(&v) {
+                Ok(u) if v.len() == 36 => V::Uuid(u),
+                _ => V::String(v),
+            })
+        }),
+        "BOOLEAN" => get_value(row, column_name, |v: bool| Ok(V::Boolean(v))),
+        "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" => {
+            get_value(row, column_name, |v: i32| Ok(V::Int32(v)))
+        }
+        "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" => {
+            get_value(row, column_name, |v: u32| Ok(V::Int32(i32::try_from(v)?)))
+        }
+        "INT UNSIGNED" => get_value(row, column_name, |v: u32| Ok(V::Int64(i64::from(v)))),
+        "YEAR" => get_unchecked_value(row, column_name, |v: u16| Ok(V::Int32(i32::from(v)))),
+        "BIT" => get_unchecked_value(row, column_name, |v: Vec<u8>| Ok(V::Binary(v))),
+        "BIGINT" => get_value(row, column_name, |v: i64| Ok(V::Int64(v))),
+        // values above i64::MAX do not fit Int64.
+        "BIGINT UNSIGNED" => get_value(row, column_name, |v: u64| {
+            Ok(V::Decimal(BigDecimal::from(v)))
+        }),
+        "FLOAT" => get_value(row, column_name, |v: f32| Ok(V::Float(v))),
+        "DOUBLE" => get_value(row, column_name, |v: f64| Ok(V::Double(v))),
+        "JSON" => get_value(row, column_name, |v: serde_json::Value| Ok(V::Json(v))),
+        "DECIMAL" => get_value(row, column_name, |v: BigDecimal| Ok(V::Decimal(v))),
+        "VARCHAR" | "CHAR" | "TEXT" | "TINYTEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" => {
+            get_value(row, column_name, |v: String| Ok(V::String(v)))
+        }
+        "BINARY" | "VARBINARY" | "BLOB" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+            get_value(row, column_name, |v: Vec<u8>| Ok(V::Binary(v)))
+        }
+        "DATE" => get_value(row, column_name, |v: NaiveDate| Ok(V::Date(v))),
+        "TIME" => get_value(row, column_name, |v: NaiveTime| Ok(V::Time(v))),
+        "DATETIME" => get_value(row, column_name, |v: NaiveDateTime| Ok(V::Timestamp(v))),
+        "TIMESTAMP" => get_value(row, column_name, |v: DateTime<Utc>| Ok(V::Timestamptz(v))),
+        unk => Err(anyhow!("cannot parse type {unk}, may not supported yet.")),
+    }
+}
+
+fn parse_mysql_type(
+    type_name: &str,
+) -> Result<(data_storages::SchemaType, HashMap<String, String>)> {
+    use data_storages::SchemaType as T;
+    let type_ = match type_name {
+        "BOOLEAN" => T::Boolean,
+        "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "TINYINT UNSIGNED" | "SMALLINT UNSIGNED"
+        | "MEDIUMINT UNSIGNED" | "YEAR" => T::Int32,
+        "INT UNSIGNED" | "BIGINT" => T::Int64,
+        "BIGINT UNSIGNED" => T::Decimal(20, 0),
+        "FLOAT" => T::Float,
+        "DOUBLE" => T::Double,
+        "JSON" => T::Json,
+        "VARCHAR" | "CHAR" | "TEXT" | "TINYTEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" => T::String,
+        "BINARY" | "VARBINARY" | "BLOB" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" | "BIT" => {
+            T::Binary
+        }
+        "DATE" => T::Date,
+        "TIME" => T::Time,
+        "DATETIME" => T::Timestamp(TimePrecision::Micro),
+        "TIMESTAMP" => T::Timestamptz(TimePrecision::Micro),
+        unk => {
+            return Err(anyhow!(
+                "unknown type {unk} from mysql row, may not supported yet."
+            ))
+        }
+    };
+    Ok((
+        type_,
+        HashMap::from([("mysql_type".to_string(), type_name.to_lowercase())]),
+    ))
+}
+
+pub fn parse_row_schema(row: &MySqlRow, uuid_types: UuidTypes) -> Result<data_storages::Schema> {
+    Ok(data_storages::Schema(
+        row.columns()
+            .iter()
+            .map(|column| {
+                let type_name = column.type_info().name();
+                let (type_, extra) = match type_name {
+                    // precision, scale and length are not in row metadata, take them from the
+                    // value.
+                    type_name if type_name == "DECIMAL" || uuid_types.may_contain(type_name) => {
+                        let type_ =
+                            parse_col_to_typed_value(type_name, column.name(), row, uuid_types)?
+                                .schema_type();
+                        let mut extra = type_.extra();
+                        extra.insert("mysql_type".to_string(), type_name.to_lowercase());
+                        (type_, extra)
+                    }
+                    _ => parse_mysql_type(type_name)?,
+                };
+                Ok(data_storages::SchemaField {
+                    name: column.name().to_string(),
+                    type_,
+                    extra,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+pub struct ColumnSchemaInDB {
+    column_name: String,
+    data_type: String,
+    column_type: String,
+    is_nullable: Option<String>,
+    character_maximum_length: Option<i64>,
+    numeric_precision: Option<i64>,
+    numeric_scale: Option<i64>,
+}
+
+impl From<MySqlRow> for ColumnSchemaInDB {
+    fn from(value: MySqlRow) -> Self {
+        ColumnSchemaInDB {
+            column_name: value.get("column_name"),
+            data_type: value.get("data_type"),
+            column_type: value.get("column_type"),
+            is_nullable: value.get("is_nullable"),
+            character_maximum_length: value.get("character_maximum_length"),
+            numeric_precision: value.get("numeric_precision"),
+            numeric_scale: value.get("numeric_scale"),
+        }
+    }
+}
+
+impl ColumnSchemaInDB {
+    pub fn to_data_schema(&self, uuid_types: UuidTypes) -> Result<data_storages::SchemaField> {
+        let mut extra: HashMap<String, String> =
+            HashMap::from([("mysql_type".to_string(), self.column_type.clone())]);
+        if let Some(nullable) = &self.is_nullable {
+            extra.insert("nullable".to_string(), utils::bool_str(nullable == "YES"));
+        }
+        if let Some(length) = self.character_maximum_length {
+            extra.insert("length".to_string(), length.to_string());
+        }
+        let unsigned = self.column_type.ends_with("unsigned");
+        let type_ = match self.data_type.as_str() {
+            "binary" if uuid_types.binary && self.column_type == "binary(16)" => {
+                data_storages::SchemaType::Uuid
+            }
+            "char" if uuid_types.char && self.column_type == "char(36)" => {
+                data_storages::SchemaType::Uuid
+            }
+            "tinyint" if self.column_type == "tinyint(1)" => data_storages::SchemaType::Boolean,
+            "tinyint" | "smallint" | "mediumint" | "year" => data_storages::SchemaType::Int32,
+            "int" if !unsigned => data_storages::SchemaType::Int32,
+            // u64::MAX has 20 digits.
+            "bigint" if unsigned => data_storages::SchemaType::Decimal(20, 0),
+            "int" | "bigint" => data_storages::SchemaType::Int64,
+            "float" => data_storages::SchemaType::Float,
+            "double" => data_storages::SchemaType::Double,
+            "json" => data_storages::SchemaType::Json,
+            "decimal" => data_storages::SchemaType::Decimal(
+                u16::try_from(self.numeric_precision.unwrap_or_default())?,
+                i16::try_from(self.numeric_scale.unwrap_or_default())?,
+            ),
+            // values of set are returned as text of comma separated members.
+            "char" | "varchar" | "tinytext" | "text" | "mediumtext" | "longtext" | "enum"
+            | "set" => data_storages::SchemaType::String,
+            "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob" | "bit" => {
+                data_storages::SchemaType::Binary
+            }
+            "date" => data_storages::SchemaType::Date,
+            "time" => data_storages::SchemaType::Time,
+            "datetime" => data_storages::SchemaType::Timestamp(TimePrecision::Micro),
+            "timestamp" => data_storages::SchemaType::Timestamptz(TimePrecision::Micro),
+            unk => return Err(anyhow!("cannot parse type {unk}, may not supported yet.")),
+        };
+        extra.extend(type_.extra());
+        Ok(data_storages::SchemaField {
+            name: self.column_name.clone(),
+            type_,
+            extra,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_storages::SchemaType as T;
+
+    fn column_type(data_type: &str, column_type: &str) -> T {
+        ColumnSchemaInDB {
+            column_name: "c".to_string(),
+            data_type: data_type.to_string(),
+            column_type: column_type.to_string(),
+            is_nullable: None,
+            character_maximum_length: None,
+            numeric_precision: None,
+            numeric_scale: None,
+        }
+        .to_data_schema(UuidTypes {
+            binary: false,
+            char: false,
+        })
+        .unwrap()
+        .type_
+    }
+
+    #[test]
+    fn unsigned_integers_fit_their_types() {
+        assert_eq!(column_type("int", "int"), T::Int32);
+        assert_eq!(column_type("int", "int unsigned"), T::Int64);
+        assert_eq!(column_type("bigint", "bigint"), T::Int64);
+        assert_eq!(column_type("bigint", "bigint unsigned"), T::Decimal(20, 0));
+        assert_eq!(parse_mysql_type("INT UNSIGNED").unwrap().0, T::Int64);
+        assert_eq!(
+            parse_mysql_type("BIGINT UNSIGNED").unwrap().0,
+            T::Decimal(20, 0)
+        );
+    }
+
+    #[test]
+    fn year_bit_and_set_are_supported() {
+        assert_eq!(column_type("year", "year"), T::Int32);
+        assert_eq!(column_type("bit", "bit(8)"), T::Binary);
+        assert_eq!(column_type("set", "set('a','b')"), T::String);
+        assert_eq!(parse_mysql_type("YEAR").unwrap().0, T::Int32);
+        assert_eq!(parse_mysql_type("BIT").unwrap().0, T::Binary);
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use parquet::{
+    arrow::{
+        arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+        ArrowWriter,
+    },
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+
+use super::{
+    arrow_convert::{arrow_to_schema, batch_to_rows, rows_to_batch, schema_to_arrow},
+    data_storages::{
+        CoercionPolicy, ReadResult, Row, Schema, SchemaField, SchemaType, SchemaTypeWithValue,
+    },
+    error::ParameterError,
+    utils::{extract_file_uri_path, option_parse},
+    DataStorage,
+};
+
+const DEFAULT_ROW_GROUP_SIZE: usize = 1024 * 1024;
+
+pub struct ParquetDataStorage {
+    file: String,
+    // reader kept between `chunk_read` calls.
+    chunk_reader: Option<ChunkReader>,
+    // writer and the schema of rows it writes, created on first write.
+    writer: Option<(ArrowWriter<fs::File>, Schema, SchemaRef)>,
+}
+
+struct ChunkReader {
+    reader: ParquetRecordBatchReader,
+    schema: Schema,
+    // number of rows of each row group.
+    row_group_rows: Vec<usize>,
+    // rows read but not returned yet.
+    pending: VecDeque<Row>,
+    // row group and offset in it of the next row to return.
+    position: (usize, usize),
+}
+
+impl ChunkReader {
+    // position moved forward by `rows`, it stays at the start of a non-empty row group.
+    fn advance(&mut self, rows: usize) {
+        let (mut row_group, mut offset) = self.position;
+        offset += rows;
+        while row_group < self.row_group_rows.len() && offset >= self.row_group_rows[row_group] {
+            offset -= self.row_group_rows[row_group];
+            row_group += 1;
+        }
+        self.position = (row_group, offset);
+    }
+}
+
+impl ParquetDataStorage {
+    pub fn new(uri: &str) -> Self {
+        ParquetDataStorage {
+            file: extract_file_uri_path(uri, "file+parquet"),
+            chunk_reader: None,
+            writer: None,
+        }
+    }
+
+    // reader of rows from `position`, whose batches have at most `limit` rows.
+    fn chunk_reader(&self, position: (usize, usize), limit: u32) -> Result<ChunkReader> {
+        let builder = self.reader_builder()?;
+        let schema = arrow_to_schema(builder.schema())?;
+        let row_group_rows = builder
+            .metadata()
+            .row_groups()
+            .iter()
+            .map(|row_group| usize::try_from(row_group.num_rows()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (row_group, offset) = position;
+        let reader = builder
+            .with_row_groups((row_group.min(row_group_rows.len())..row_group_rows.len()).collect())
+            .with_offset(offset)
+            .with_batch_size(limit.max(1) as usize)
+            .build()?;
+        Ok(ChunkReader {
+            reader,
+            schema,
+            row_group_rows,
+            pending: VecDeque::new(),
+            position,
+        })
+    }
+
+    fn reader_builder(&self) -> Result<ParquetRecordBatchReaderBuilder<fs::File>> {
+        Ok(ParquetRecordBatchReaderBuilder::try_new(fs::File::open(
+            &self.file,
+        )?)?)
+    }
+}
+
+fn writer_properties(options: &HashMap<&str, &str>) -> Result<WriterProperties> {
+    let compression = match options.get("compression") {
+        Some(compression) => compression.parse::<Compression>()?,
+        None => Compression::SNAPPY,
+    };
+    let row_group_size = option_parse(options, "row_group_size", DEFAULT_ROW_GROUP_SIZE)?;
+    if row_group_size == 0 {
+        return Err(ParameterError::new("`row_group_size` must greater than zero").into());
+    }
+    Ok(WriterProperties::builder()
+        .set_compression(compression)
+        .set_max_row_group_size(row_group_size)
+        .build())
+}
+
+// parquet cannot keep intervals of months, days and nanoseconds, they are written as ISO 8601
+// text.
+fn parquet_type(type_: &SchemaType) -> SchemaType {
+    match type_ {
+        SchemaType::Interval => SchemaType::String,
+        SchemaType::List(inner) => SchemaType::List(Box::new(parquet_type(inner))),
+        SchemaType::Struct(fields) => SchemaType::Struct(
+            fields
+                .iter()
+                .map(|field| SchemaField {
+                    type_: parquet_type(&field.type_),
+                    ..field.clone()
+                })
+                .collect(),
+        ),
+        type_ => type_.clone(),
+    }
+}
+
+#[async_trait]
+impl DataStorage for ParquetDataStorage {
+    /// schema embedded in the parquet file.
+    async fn read_schema(&mut self, _: &HashMap<&str, &str>) -> Result<Schema> {
+        arrow_to_schema(self.reader_builder()?.schema())
+    }
+
+    async fn read(&mut self, _: &HashMap<&str, &str>) -> Result<ReadResult> {
+        let builder = self.reader_builder()?;
+        let schema = arrow_to_schema(builder.schema())?;
+        let mut data: Vec<Row> = Vec::new();
+        for batch in builder.build()? {
+            data.extend(batch_to_rows(&batch?)?);
+        }
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor: None,
+        })
+    }
+
+    /// each chunk has at most `limit` rows. The cursor is the row group and the offset in it of
+    /// the next row, the reader is kept if the next call continues from its cursor.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        _: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let position = match cursor {
+            None => (0, 0),
+            Some(SchemaTypeWithValue::List(position)) => match position.as_slice() {
+                [SchemaTypeWithValue::Int64(row_group), SchemaTypeWithValue::Int64(offset)] => {
+                    (usize::try_from(*row_group)?, usize::try_from(*offset)?)
+                }
+                _ => {
+                    return Err(ParameterError::new(
+                        format!("invalid cursor {position:?} for parquet").as_str(),
+                    )
+                    .into())
+                }
+            },
+            Some(unk) => {
+                return Err(ParameterError::new(
+                    format!(
+                        "invalid cursor {unk:?} for parquet, must be a row group and an offset"
+                    )
+                    .as_str(),
+                )
+                .into())
+            }
+        };
+        let mut chunk_reader = match self.chunk_reader.take() {
+            Some(chunk_reader) if chunk_reader.position == position => chunk_reader,
+            _ => self.chunk_reader(position, limit)?,
+        };
+        while chunk_reader.pending.len() < limit as usize {
+            match chunk_reader.reader.next() {
+                Some(batch) => chunk_reader.pending.extend(batch_to_rows(&batch?)?),
+                None => break,
+            }
+        }
+        let take = chunk_reader.pending.len().min(limit as usize);
+        let data = chunk_reader.pending.drain(..take).collect::<Vec<_>>();
+        chunk_reader.advance(data.len());
+        let schema = chunk_reader.schema.clone();
+        // empty data means the end of file.
+        let cursor = if data.is_empty() {
+            None
+        } else {
+            let (row_group, offset) = chunk_reader.position;
+            self.chunk_reader = Some(chunk_reader);
+            Some(SchemaTypeWithValue::List(vec![
+                SchemaTypeWithValue::Int64(i64::try_from(row_group)?),
+                SchemaTypeWithValue::Int64(i64::try_from(offset)?),
+            ]))
+        };
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor,
+        })
+    }
+
+    /// rows are buffered into row groups of `row_group_size`, the file is completed on `close`.
+    /// Intervals are written as ISO 8601 text.
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        if self.writer.is_none() {
+            let schema = match (schema, data.first()) {
+                (Some(schema), _) => schema,
+                (None, Some(row)) => row.schema(),
+                (None, None) => return Ok(()),
+            };
+            let schema = Schema(
+                schema
+                    .0
+                    .into_iter()
+                    .map(|field| SchemaField {
+                        type_: parquet_type(&field.type_),
+                        ..field
+                    })
+                    .collect(),
+            );
+            let file = fs::File::create(&self.file)?;
+            let arrow_schema = Arc::new(schema_to_arrow(&schema)?);
+            let writer = ArrowWriter::try_new(
+                file,
+                arrow_schema.clone(),
+                Some(writer_properties(options)?),
+            )?;
+            self.writer = Some((writer, schema, arrow_schema));
+        }
+        let (writer, schema, arrow_schema) = self.writer.as_mut().unwrap();
+        if !data.is_empty() {
+            let policy = option_parse(options, "coercion", CoercionPolicy::default())?;
+            writer.write(&rows_to_batch(&data, schema, arrow_schema.clone(), policy)?)?;
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        if let Some((writer, _, _)) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::arrow_convert::tests::typed_rows;
+
+    #[tokio::test]
+    async fn write_and_read_row_groups() {
+        let (schema, rows) = typed_rows();
+        let path = std::env::temp_dir()
+            .join(format!("datawhirr-parquet-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let uri = format!("file+parquet://{path}");
+        let options = HashMap::from([("row_group_size", "1"), ("compression", "zstd(3)")]);
+        let mut storage = ParquetDataStorage::new(uri.as_str());
+        storage
+            .write(rows.clone(), Some(schema.clone()), &options)
+            .await
+            .unwrap();
+        storage.close().await.unwrap();
+
+        let mut storage = ParquetDataStorage::new(uri.as_str());
+        let all = storage.read(&options).await.unwrap();
+        let mut chunks = vec![];
+        let mut cursors = vec![];
+        let mut cursor = None;
+        for limit in [1, 10] {
+            let chunk = storage.chunk_read(cursor, limit, &options).await.unwrap();
+            cursor = chunk.cursor.clone();
+            cursors.push(chunk.cursor);
+            chunks.push(chunk.data);
+        }
+        // a new reader starts from the offset in row group.
+        let cursor = SchemaTypeWithValue::List(vec![
+            SchemaTypeWithValue::Int64(0),
+            SchemaTypeWithValue::Int64(1),
+        ]);
+        let resumed = ParquetDataStorage::new(uri.as_str())
+            .chunk_read(Some(cursor), 10, &options)
+            .await
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        // intervals are read back as their ISO 8601 text.
+        let mut rows = rows;
+        for row in rows.iter_mut() {
+            for column in row.0.iter_mut() {
+                if let SchemaTypeWithValue::Interval(interval) = column.value {
+                    column.value = SchemaTypeWithValue::String(interval.to_iso8601());
+                }
+            }
+        }
+        assert_eq!(all.data, rows);
+        let types = |schema: &Schema| {
+            schema
+                .0
+                .iter()
+                .map(|f| parquet_type(&f.type_))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(types(&all.schema), types(&schema));
+        // chunks are bounded by limit, not by row groups of one row.
+        assert_eq!(chunks, vec![rows[..1].to_vec(), rows[1..].to_vec()]);
+        let position = |row_group, offset| {
+            Some(SchemaTypeWithValue::List(vec![
+                SchemaTypeWithValue::Int64(row_group),
+                SchemaTypeWithValue::Int64(offset),
+            ]))
+        };
+        assert_eq!(cursors, vec![position(1, 0), position(2, 0)]);
+        assert_eq!(resumed.data, rows[1..].to_vec());
+    }
+}
@@ -0,0 +1,483 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
+use num_bigint::Sign;
+
+use crate::data_storages::{
+    data_storages::{BinaryEncoding, Row, Schema, SchemaField, SchemaType, SchemaTypeWithValue},
+    error::ParameterError,
+};
+
+const BINARY_SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+// version of jsonb binary format, followed by json text.
+const JSONB_VERSION: u8 = 1;
+// seconds between unix epoch and postgres epoch(2000-01-01 00:00:00 UTC)
+const PG_EPOCH_OFFSET_SECS: i64 = 946_684_800;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyFormat {
+    Binary,
+    Text,
+}
+
+impl CopyFormat {
+    /// text by default, which postgres casts to the types of columns. Binary is faster but its
+    /// values must be of the same types as columns.
+    pub fn from_option(format: Option<&&str>) -> Result<CopyFormat> {
+        match format.map(|f| f.to_lowercase()).as_deref() {
+            None | Some("text") => Ok(CopyFormat::Text),
+            Some("binary") => Ok(CopyFormat::Binary),
+            Some(unk) => {
+                Err(ParameterError::new(format!("unknown copy format {unk}").as_str()).into())
+            }
+        }
+    }
+
+    fn sql_option(&self) -> &str {
+        match self {
+            CopyFormat::Binary => "binary",
+            CopyFormat::Text => "text",
+        }
+    }
+
+    /// bytes must be sent before any tuple.
+    pub fn header(&self) -> Vec<u8> {
+        match self {
+            CopyFormat::Binary => {
+                let mut header = BINARY_SIGNATURE.to_vec();
+                // flags field
+                header.extend(0i32.to_be_bytes());
+                // header extension area length
+                header.extend(0i32.to_be_bytes());
+                header
+            }
+            CopyFormat::Text => vec![],
+        }
+    }
+
+    /// bytes must be sent after all tuples.
+    pub fn trailer(&self) -> Vec<u8> {
+        match self {
+            CopyFormat::Binary => (-1i16).to_be_bytes().to_vec(),
+            CopyFormat::Text => vec![],
+        }
+    }
+}
+
+/// copy statement of columns in schema, which are quoted to keep their case and symbols.
+pub fn copy_statement(table: &str, schema: &Schema, format: CopyFormat) -> String {
+    let columns = schema
+        .0
+        .iter()
+        .map(|field| format!("\"{}\"", field.name.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "copy {} ({}) from stdin with (format {})",
+        table,
+        columns,
+        format.sql_option()
+    )
+}
+
+fn pg_days(date: NaiveDate) -> i32 {
+    let pg_epoch = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    (date - pg_epoch).num_days() as i32
+}
+
+fn pg_micros(datetime: &DateTime<Utc>) -> i64 {
+    (datetime.timestamp() - PG_EPOCH_OFFSET_SECS) * 1_000_000
+        + i64::from(datetime.timestamp_subsec_micros())
+}
+
+fn encode_error(value: &SchemaTypeWithValue, type_: &SchemaType) -> anyhow::Error {
+    anyhow!("cannot encode value {value:?} as {type_:?} in binary copy, try `format=text`")
+}
+
+// naive timestamps are encoded as if in UTC, dates are at midnight.
+fn timestamp_of(value: &SchemaTypeWithValue) -> Option<DateTime<Utc>> {
+    match value {
+        SchemaTypeWithValue::Timestamp(t) => Some(t.and_utc()),
+        SchemaTypeWithValue::Timestamptz(t) => Some(*t),
+        SchemaTypeWithValue::Date(d) => Some(d.and_time(NaiveTime::MIN).and_utc()),
+        _ => None,
+    }
+}
+
+fn float_text(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// text form of value which postgres input functions accept, None means NULL.
+fn value_to_text(value: &SchemaTypeWithValue) -> Result<Option<String>> {
+    Ok(match value {
+        SchemaTypeWithValue::String(s) => Some(s.clone()),
+        SchemaTypeWithValue::Int32(i) => Some(i.to_string()),
+        SchemaTypeWithValue::Int64(i) => Some(i.to_string()),
+        SchemaTypeWithValue::Binary(b) => Some(format!("\\x{}", BinaryEncoding::Hex.encode(b))),
+        SchemaTypeWithValue::Boolean(b) => Some(if *b { "t" } else { "f" }.to_string()),
+        SchemaTypeWithValue::Timestamp(t) => Some(t.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+        SchemaTypeWithValue::Timestamptz(t) => {
+            Some(t.format("%Y-%m-%d %H:%M:%S%.f+00").to_string())
+        }
+        SchemaTypeWithValue::Date(d) => Some(d.format("%Y-%m-%d").to_string()),
+        SchemaTypeWithValue::Time(t) => Some(t.format("%H:%M:%S%.f").to_string()),
+        SchemaTypeWithValue::Interval(i) => Some(i.to_iso8601()),
+        SchemaTypeWithValue::Double(f) => Some(float_text(*f)),
+        SchemaTypeWithValue::Float(f) => Some(float_text(f64::from(*f))),
+        SchemaTypeWithValue::Decimal(d) => Some(d.to_string()),
+        SchemaTypeWithValue::Uuid(u) => Some(u.hyphenated().to_string()),
+        SchemaTypeWithValue::Json(j) => Some(j.to_string()),
+        SchemaTypeWithValue::List(values) => Some(format!(
+            "{{{}}}",
+            values
+                .iter()
+                .map(|value| match value {
+                    // nested lists are the dimensions of multidimensional array.
+                    SchemaTypeWithValue::List(_) => Ok(value_to_text(value)?.unwrap()),
+                    value => quote_element(value, "NULL"),
+                })
+                .collect::<Result<Vec<_>>>()?
+                .join(",")
+        )),
+        SchemaTypeWithValue::Struct(columns) => Some(format!(
+            "({})",
+            columns
+                .iter()
+                .map(|column| quote_element(&column.value, ""))
+                .collect::<Result<Vec<_>>>()?
+                .join(",")
+        )),
+        SchemaTypeWithValue::None => None,
+    })
+}
+
+// binary send form of numeric: ndigits, weight, sign and dscale, followed by base-10000 digits
+// of which the first has weight `weight`.
+fn pg_numeric(value: &BigDecimal) -> Result<Vec<u8>> {
+    let (digits, scale) = value.as_bigint_and_exponent();
+    let sign: u16 = if digits.sign() == Sign::Minus {
+        0x4000
+    } else {
+        0
+    };
+    let mut text = digits.magnitude().to_string();
+    if scale < 0 {
+        text.push_str(&"0".repeat(usize::try_from(-scale)?));
+    }
+    let dscale = usize::try_from(scale.max(0))?;
+    // align both integer and fraction part to groups of 4 digits.
+    let fraction_len = dscale.div_ceil(4) * 4;
+    text.push_str(&"0".repeat(fraction_len - dscale));
+    let integer_len = text.len().saturating_sub(fraction_len).div_ceil(4) * 4;
+    let text = format!("{text:0>width$}", width = integer_len + fraction_len);
+    let mut groups = text
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| Ok(std::str::from_utf8(chunk)?.parse::<u16>()?))
+        .collect::<Result<Vec<_>>>()?;
+    let mut weight = i16::try_from(integer_len / 4)? - 1;
+    let leading_zeros = groups.iter().take_while(|group| **group == 0).count();
+    groups.drain(..leading_zeros);
+    weight -= i16::try_from(leading_zeros)?;
+    while groups.last() == Some(&0) {
+        groups.pop();
+    }
+    if groups.is_empty() {
+        weight = 0;
+    }
+    let mut buf = Vec::with_capacity(8 + groups.len() * 2);
+    buf.extend(i16::try_from(groups.len())?.to_be_bytes());
+    buf.extend(weight.to_be_bytes());
+    buf.extend(sign.to_be_bytes());
+    buf.extend(u16::try_from(dscale)?.to_be_bytes());
+    for group in groups {
+        buf.extend(group.to_be_bytes());
+    }
+    Ok(buf)
+}
+
+// text of value for json column, lists and structs are json rather than literals of arrays or
+// records.
+fn json_text(value: &SchemaTypeWithValue) -> Result<Option<String>> {
+    match value {
+        SchemaTypeWithValue::List(_) | SchemaTypeWithValue::Struct(_) => {
+            Ok(Some(value.to_json().to_string()))
+        }
+        value => value_to_text(value),
+    }
+}
+
+// element of array literal or field of record literal, which is always quoted unless it is
+// `null`.
+fn quote_element(value: &SchemaTypeWithValue, null: &str) -> Result<String> {
+    Ok(match value_to_text(value)? {
+        Some(text) => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => null.to_string(),
+    })
+}
+
+fn escape_text(text: &str, buf: &mut Vec<u8>) {
+    for byte in text.bytes() {
+        match byte {
+            b'\\' => buf.extend(b"\\\\"),
+            b'\n' => buf.extend(b"\\n"),
+            b'\r' => buf.extend(b"\\r"),
+            b'\t' => buf.extend(b"\\t"),
+            _ => buf.push(byte),
+        }
+    }
+}
+
+// binary send form of value for column typed `type_`, None means NULL. Values are cast to the
+// type if they could be, e.g. Int64 of jsonl into int4.
+fn value_to_binary(value: &SchemaTypeWithValue, field: &SchemaField) -> Result<Option<Vec<u8>>> {
+    if let SchemaTypeWithValue::None = value {
+        return Ok(None);
+    }
+    let type_ = &field.type_;
+    let cast = value.cast(type_);
+    let value = cast.as_ref().unwrap_or(value);
+    Ok(Some(match (type_, value) {
+        (SchemaType::String, value) => value_to_text(value)?.unwrap().into_bytes(),
+        (SchemaType::Int32, SchemaTypeWithValue::Int32(i))
+            if field.extra.get("length").map(String::as_str) == Some("2") =>
+        {
+            i16::try_from(*i)?.to_be_bytes().to_vec()
+        }
+        (SchemaType::Int64, SchemaTypeWithValue::Int64(i))
+            if field.extra.get("length").map(String::as_str) == Some("4") =>
+        {
+            u32::try_from(*i)?.to_be_bytes().to_vec()
+        }
+        (SchemaType::Int32, SchemaTypeWithValue::Int32(i)) => i.to_be_bytes().to_vec(),
+        (SchemaType::Int64, SchemaTypeWithValue::Int32(i)) => i64::from(*i).to_be_bytes().to_vec(),
+        (SchemaType::Int64, SchemaTypeWithValue::Int64(i)) => i.to_be_bytes().to_vec(),
+        (SchemaType::Binary, SchemaTypeWithValue::Binary(b)) => b.clone(),
+        (SchemaType::Binary, SchemaTypeWithValue::String(s)) => s.as_bytes().to_vec(),
+        (SchemaType::Boolean, SchemaTypeWithValue::Boolean(b)) => vec![u8::from(*b)],
+        (SchemaType::Float, SchemaTypeWithValue::Float(f)) => f.to_be_bytes().to_vec(),
+        (SchemaType::Double, SchemaTypeWithValue::Float(f)) => f64::from(*f).to_be_bytes().to_vec(),
+        (SchemaType::Double, SchemaTypeWithValue::Double(f)) => f.to_be_bytes().to_vec(),
+        (SchemaType::Decimal(..), SchemaTypeWithValue::Decimal(d)) => pg_numeric(d)?,
+        // json is sent as text, jsonb has a version before the text. Strings are taken as json
+        // text.
+        (SchemaType::Json, value)
+            if field.extra.get("pg_type").map(String::as_str) == Some("json") =>
+        {
+            json_text(value)?.unwrap().into_bytes()
+        }
+        (SchemaType::Json, value) => {
+            let mut bytes = vec![JSONB_VERSION];
+            bytes.extend(json_text(value)?.unwrap().into_bytes());
+            bytes
+        }
+        (SchemaType::Uuid, value) => match value.as_uuid() {
+            Some(u) => u.as_bytes().to_vec(),
+            None => return Err(anyhow!("cannot encode value {value:?} as uuid")),
+        },
+        (SchemaType::Date, value) => match timestamp_of(value) {
+            Some(d) => pg_days(d.date_naive()).to_be_bytes().to_vec(),
+            None => return Err(encode_error(value, type_)),
+        },
+        // nanoseconds are truncated, postgres keeps microseconds. Naive values have no time zone
+        // for `timestamptz`.
+        (SchemaType::Timestamp(_), value) => match timestamp_of(value) {
+            Some(d) => pg_micros(&d).to_be_bytes().to_vec(),
+            None => return Err(encode_error(value, type_)),
+        },
+        (SchemaType::Timestamptz(_), SchemaTypeWithValue::Timestamptz(t)) => {
+            pg_micros(t).to_be_bytes().to_vec()
+        }
+        (SchemaType::Time, SchemaTypeWithValue::Time(t)) => {
+            (i64::from(t.num_seconds_from_midnight()) * 1_000_000
+                + i64::from(t.nanosecond() / 1000))
+            .to_be_bytes()
+            .to_vec()
+        }
+        (SchemaType::Interval, SchemaTypeWithValue::Interval(i)) => {
+            let mut bytes = i.microseconds.to_be_bytes().to_vec();
+            bytes.extend(i.days.to_be_bytes());
+            bytes.extend(i.months.to_be_bytes());
+            bytes
+        }
+        (type_, value) => return Err(encode_error(value, type_)),
+    }))
+}
+
+/// encode one row as a copy tuple, the columns are ordered by schema and the columns not in row
+/// will be written as NULL.
+pub fn encode_row(row: &Row, schema: &Schema, format: CopyFormat, buf: &mut Vec<u8>) -> Result<()> {
+    match format {
+        CopyFormat::Binary => {
+            let field_count = i16::try_from(schema.0.len())?;
+            buf.extend(field_count.to_be_bytes());
+            for field in &schema.0 {
+                let value = row.get(&field.name).unwrap_or(&SchemaTypeWithValue::None);
+                match value_to_binary(value, field)? {
+                    Some(bytes) => {
+                        buf.extend(i32::try_from(bytes.len())?.to_be_bytes());
+                        buf.extend(bytes);
+                    }
+                    None => buf.extend((-1i32).to_be_bytes()),
+                }
+            }
+        }
+        CopyFormat::Text => {
+            for (idx, field) in schema.0.iter().enumerate() {
+                if idx > 0 {
+                    buf.push(b'\t');
+                }
+                let value = row.get(&field.name).unwrap_or(&SchemaTypeWithValue::None);
+                let text = match field.type_ {
+                    SchemaType::Json => json_text(value)?,
+                    _ => value_to_text(value)?,
+                };
+                match text {
+                    Some(text) => escape_text(&text, buf),
+                    None => buf.extend(b"\\N"),
+                }
+            }
+            buf.push(b'\n');
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::data_storages::{Column, TimePrecision};
+    use std::collections::HashMap;
+
+    fn field(name: &str, type_: SchemaType) -> SchemaField {
+        SchemaField {
+            name: name.to_string(),
+            type_,
+            extra: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn numeric_send_form() {
+        // expected bytes are from `numeric_send` of postgres.
+        for (value, expected) in [
+            ("12345.678", "0003000100000003000109291a7c"),
+            ("-0.0001", "0001ffff400000040001"),
+            ("0", "0000000000000000"),
+            ("1.5E+5", "0001000100000000000f"),
+            ("1.50", "000200000000000200011388"),
+        ] {
+            let bytes = pg_numeric(&value.parse().unwrap()).unwrap();
+            let hex = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+            assert_eq!(hex, expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn copy_statement_quotes_columns() {
+        let schema = Schema(vec![
+            field("id", SchemaType::Int64),
+            field("Name", SchemaType::String),
+            field("a\"b", SchemaType::String),
+        ]);
+        assert_eq!(
+            copy_statement("public.t", &schema, CopyFormat::Text),
+            r#"copy public.t ("id", "Name", "a""b") from stdin with (format text)"#
+        );
+    }
+
+    #[test]
+    fn text_json_of_nested_values() {
+        let schema = Schema(vec![
+            field("tags", SchemaType::List(Box::new(SchemaType::String))),
+            field("doc", SchemaType::Json),
+        ]);
+        let tags = SchemaTypeWithValue::List(vec![
+            SchemaTypeWithValue::String("a b".to_string()),
+            SchemaTypeWithValue::None,
+        ]);
+        let row = Row(vec![
+            Column {
+                name: "tags".to_string(),
+                value: tags.clone(),
+            },
+            Column {
+                name: "doc".to_string(),
+                value: tags,
+            },
+        ]);
+        let mut buf = Vec::new();
+        encode_row(&row, &schema, CopyFormat::Text, &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"a b\",NULL}\t[\"a b\",null]\n"
+        );
+    }
+
+    #[test]
+    fn binary_cast_to_column_type() {
+        let mut int2 = field("i", SchemaType::Int32);
+        int2.extra.insert("length".to_string(), "2".to_string());
+        assert_eq!(
+            value_to_binary(&SchemaTypeWithValue::Int64(7), &int2).unwrap(),
+            Some(vec![0, 7])
+        );
+        assert_eq!(
+            value_to_binary(
+                &SchemaTypeWithValue::Int64(2),
+                &field("d", SchemaType::Double)
+            )
+            .unwrap(),
+            Some(2f64.to_be_bytes().to_vec())
+        );
+        assert!(value_to_binary(&SchemaTypeWithValue::Double(1.5), &int2).is_err());
+        // naive timestamps are not taken as UTC for timestamptz.
+        let epoch = SchemaTypeWithValue::Timestamp(
+            DateTime::from_timestamp(PG_EPOCH_OFFSET_SECS, 0)
+                .unwrap()
+                .naive_utc(),
+        );
+        assert_eq!(
+            value_to_binary(
+                &epoch,
+                &field("t", SchemaType::Timestamp(TimePrecision::Micro))
+            )
+            .unwrap(),
+            Some(vec![0; 8])
+        );
+        assert!(value_to_binary(
+            &epoch,
+            &field("t", SchemaType::Timestamptz(TimePrecision::Micro))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn binary_json_by_pg_type() {
+        let value = SchemaTypeWithValue::Json(serde_json::json!({"a": 1}));
+        let mut json = field("j", SchemaType::Json);
+        json.extra.insert("pg_type".to_string(), "json".to_string());
+        let mut jsonb = field("j", SchemaType::Json);
+        jsonb
+            .extra
+            .insert("pg_type".to_string(), "jsonb".to_string());
+        assert_eq!(
+            value_to_binary(&value, &json).unwrap(),
+            Some(br#"{"a":1}"#.to_vec())
+        );
+        assert_eq!(
+            value_to_binary(&value, &jsonb).unwrap(),
+            Some(b"\x01{\"a\":1}".to_vec())
+        );
+        // jsonb by default, e.g. schema of other storages.
+        assert_eq!(
+            value_to_binary(&value, &field("j", SchemaType::Json)).unwrap(),
+            Some(b"\x01{\"a\":1}".to_vec())
+        );
+    }
+}
@@ -1,5 +1,4 @@
-mod error;
+mod copy;
 mod parser;
 mod pg;
-mod utils;
 pub use pg::PgSqlStorage;
@@ -1,36 +1,266 @@
 use anyhow::{anyhow, Result};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use std::collections::HashMap;
+use uuid::Uuid;
 
-use crate::data_storages::{data_storages, pgsql::utils};
-use sqlx::postgres::PgRow;
-use sqlx::{Column, Row};
+use crate::data_storages::{
+    data_storages::{self, Interval, TimePrecision},
+    utils,
+};
+use sqlx::postgres::{
+    types::{Oid, PgInterval, PgRecordDecoder},
+    PgRow, PgTypeInfo, PgTypeKind, PgValueFormat,
+};
+use sqlx::{Column, Decode, Row, ValueRef};
 
+// built-in scalar types as (udt name, type name of row, type), besides numeric whose type
+// depends on precision and scale.
+const PG_SCALAR_TYPES: &[(&str, &str, data_storages::SchemaType)] = &[
+    ("bool", "BOOL", data_storages::SchemaType::Boolean),
+    ("int2", "INT2", data_storages::SchemaType::Int32),
+    ("int4", "INT4", data_storages::SchemaType::Int32),
+    ("int8", "INT8", data_storages::SchemaType::Int64),
+    ("oid", "OID", data_storages::SchemaType::Int64),
+    ("float4", "FLOAT4", data_storages::SchemaType::Float),
+    ("float8", "FLOAT8", data_storages::SchemaType::Double),
+    ("text", "TEXT", data_storages::SchemaType::String),
+    ("varchar", "VARCHAR", data_storages::SchemaType::String),
+    // type name of bpchar is `CHAR`, and the single byte `"char"` is `"CHAR"`.
+    ("bpchar", "CHAR", data_storages::SchemaType::String),
+    ("char", "\"CHAR\"", data_storages::SchemaType::String),
+    ("name", "NAME", data_storages::SchemaType::String),
+    ("uuid", "UUID", data_storages::SchemaType::Uuid),
+    ("bytea", "BYTEA", data_storages::SchemaType::Binary),
+    ("date", "DATE", data_storages::SchemaType::Date),
+    ("time", "TIME", data_storages::SchemaType::Time),
+    (
+        "timestamp",
+        "TIMESTAMP",
+        data_storages::SchemaType::Timestamp(TimePrecision::Micro),
+    ),
+    (
+        "timestamptz",
+        "TIMESTAMPTZ",
+        data_storages::SchemaType::Timestamptz(TimePrecision::Micro),
+    ),
+    ("interval", "INTERVAL", data_storages::SchemaType::Interval),
+    ("json", "JSON", data_storages::SchemaType::Json),
+    ("jsonb", "JSONB", data_storages::SchemaType::Json),
+];
+
+// expands `$decode!(rust type, constructor of value)` of type `$type_name`, which is shared by
+// scalars, array elements and composite fields. Interval is not decoded by sqlx in text format,
+// see `interval_value`.
+macro_rules! by_type_name {
+    ($type_name:expr, $decode:ident) => {
+        match $type_name {
+            "BOOL" => $decode!(bool, data_storages::SchemaTypeWithValue::Boolean),
+            "INT2" => $decode!(i16, |v: i16| data_storages::SchemaTypeWithValue::Int32(
+                i32::from(v)
+            )),
+            "INT4" => $decode!(i32, data_storages::SchemaTypeWithValue::Int32),
+            "INT8" => $decode!(i64, data_storages::SchemaTypeWithValue::Int64),
+            "OID" => $decode!(Oid, |v: Oid| data_storages::SchemaTypeWithValue::Int64(
+                i64::from(v.0)
+            )),
+            "FLOAT4" => $decode!(f32, data_storages::SchemaTypeWithValue::Float),
+            "FLOAT8" => $decode!(f64, data_storages::SchemaTypeWithValue::Double),
+            "NUMERIC" => $decode!(BigDecimal, data_storages::SchemaTypeWithValue::Decimal),
+            "TEXT" | "VARCHAR" | "CHAR" | "NAME" => {
+                $decode!(String, data_storages::SchemaTypeWithValue::String)
+            }
+            "\"CHAR\"" => $decode!(i8, |v: i8| data_storages::SchemaTypeWithValue::String(
+                char::from(v as u8).to_string()
+            )),
+            "UUID" => $decode!(Uuid, data_storages::SchemaTypeWithValue::Uuid),
+            "BYTEA" => $decode!(Vec<u8>, data_storages::SchemaTypeWithValue::Binary),
+            "DATE" => $decode!(NaiveDate, data_storages::SchemaTypeWithValue::Date),
+            "TIME" => $decode!(NaiveTime, data_storages::SchemaTypeWithValue::Time),
+            "TIMESTAMP" => $decode!(NaiveDateTime, data_storages::SchemaTypeWithValue::Timestamp),
+            "TIMESTAMPTZ" => {
+                $decode!(
+                    DateTime<Utc>,
+                    data_storages::SchemaTypeWithValue::Timestamptz
+                )
+            }
+            "JSON" | "JSONB" => {
+                $decode!(serde_json::Value, data_storages::SchemaTypeWithValue::Json)
+            }
+            unk => return Err(anyhow!("cannot parse type {unk}, may not supported yet.")),
+        }
+    };
+}
+
+// one dimensional array as list.
+fn array_value(
+    element_type: &str,
+    column_name: &str,
+    row: &PgRow,
+) -> Result<data_storages::SchemaTypeWithValue> {
+    use data_storages::SchemaTypeWithValue as V;
+    macro_rules! decode_array {
+        ($type_:ty, $to_value:expr) => {
+            row.try_get::<Option<Vec<Option<$type_>>>, _>(column_name)?
+                .map_or(V::None, |values| {
+                    V::List(
+                        values
+                            .into_iter()
+                            .map(|value| value.map_or(V::None, $to_value))
+                            .collect(),
+                    )
+                })
+        };
+    }
+    Ok(by_type_name!(element_type, decode_array))
+}
+
+// composite value as struct, nested composite fields are not supported.
+fn composite_value(
+    fields: &[(String, PgTypeInfo)],
+    column_name: &str,
+    row: &PgRow,
+) -> Result<data_storages::SchemaTypeWithValue> {
+    use data_storages::SchemaTypeWithValue as V;
+    let raw = row.try_get_raw(column_name)?;
+    if raw.is_null() {
+        return Ok(V::None);
+    }
+    let mut decoder = PgRecordDecoder::new(raw).map_err(|err| anyhow!(err))?;
+    macro_rules! decode_field {
+        ($type_:ty, $to_value:expr) => {
+            decoder
+                .try_decode::<Option<$type_>>()
+                .map_err(|err| anyhow!(err))?
+                .map_or(V::None, $to_value)
+        };
+    }
+    Ok(V::Struct(
+        fields
+            .iter()
+            .map(|(name, type_info)| {
+                Ok(data_storages::Column {
+                    name: name.clone(),
+                    value: by_type_name!(type_info.to_string().as_str(), decode_field),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+// interval in the default `postgres` style, e.g. `1 year 2 mons -3 days +04:05:06.5`.
+fn parse_pg_interval(text: &str) -> Result<Interval> {
+    let invalid = || {
+        anyhow!("cannot parse interval `{text}`, only the `postgres` interval style is supported.")
+    };
+    let mut interval = Interval {
+        months: 0,
+        days: 0,
+        microseconds: 0,
+    };
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token.contains(':') {
+            let (negative, clock) = match token.strip_prefix('-') {
+                Some(clock) => (true, clock),
+                None => (false, token.trim_start_matches('+')),
+            };
+            let mut parts = clock.split(':');
+            let (Some(hours), Some(minutes), Some(seconds), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid());
+            };
+            let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+            let number = |part: &str| part.parse::<i64>().map_err(|_| invalid());
+            let microseconds = ((number(hours)? * 60 + number(minutes)?) * 60 + number(seconds)?)
+                * 1_000_000
+                + number(&format!("{fraction:0<6}"))?;
+            interval.microseconds += if negative {
+                -microseconds
+            } else {
+                microseconds
+            };
+        } else {
+            let value = token.parse::<i32>().map_err(|_| invalid())?;
+            match tokens.next() {
+                Some("year" | "years") => interval.months += value * 12,
+                Some("mon" | "mons") => interval.months += value,
+                Some("day" | "days") => interval.days += value,
+                _ => return Err(invalid()),
+            }
+        }
+    }
+    Ok(interval)
+}
+
+// sqlx does not decode interval in text format, which rows fetched by simple query protocol are
+// in.
+fn interval_value(column_name: &str, row: &PgRow) -> Result<data_storages::SchemaTypeWithValue> {
+    let raw = row.try_get_raw(column_name)?;
+    if raw.is_null() {
+        return Ok(data_storages::SchemaTypeWithValue::None);
+    }
+    let interval = match raw.format() {
+        PgValueFormat::Binary => {
+            let interval = PgInterval::decode(raw).map_err(|err| anyhow!(err))?;
+            Interval {
+                months: interval.months,
+                days: interval.days,
+                microseconds: interval.microseconds,
+            }
+        }
+        PgValueFormat::Text => parse_pg_interval(raw.as_str().map_err(|err| anyhow!(err))?)?,
+    };
+    Ok(data_storages::SchemaTypeWithValue::Interval(interval))
+}
+
+/// value of column typed by `type_name`, NULL is `SchemaTypeWithValue::None`.
 pub fn parse_col_to_typed_value(
     type_name: &str,
     column_name: &str,
     row: &PgRow,
 ) -> Result<data_storages::SchemaTypeWithValue> {
-    match type_name {
-        "VARCHAR" => Ok(data_storages::SchemaTypeWithValue::String(
-            row.get(column_name),
-        )),
-        "INT4" => Ok(data_storages::SchemaTypeWithValue::Int32(
-            row.get(column_name),
-        )),
-        unk => Err(anyhow!("cannot parse type {unk}, may not supported yet.")),
+    macro_rules! decode_value {
+        ($type_:ty, $to_value:expr) => {
+            row.try_get::<Option<$type_>, _>(column_name)?
+                .map_or(data_storages::SchemaTypeWithValue::None, $to_value)
+        };
+    }
+    match (
+        type_name,
+        type_name.strip_suffix("[]"),
+        row.column(column_name).type_info().kind(),
+    ) {
+        ("INTERVAL", _, _) => interval_value(column_name, row),
+        (_, Some(element_type), _) => array_value(element_type, column_name, row),
+        (_, None, PgTypeKind::Composite(fields)) => composite_value(fields, column_name, row),
+        _ => Ok(by_type_name!(type_name, decode_value)),
     }
 }
 
 fn parse_pg_type(type_name: &str) -> Result<(data_storages::SchemaType, HashMap<String, String>)> {
-    match type_name {
-        "VARCHAR" => Ok((data_storages::SchemaType::String, HashMap::new())),
-        "INT4" => Ok((
-            data_storages::SchemaType::Int32,
-            HashMap::from([("length".to_string(), "4".to_string())]),
-        )),
-        unk => Err(anyhow!(
-            "unknown type {unk} from pg row, may not supported yet."
-        )),
+    if let Some(element_type) = type_name.strip_suffix("[]") {
+        return Ok((
+            data_storages::SchemaType::List(Box::new(parse_pg_type(element_type)?.0)),
+            HashMap::new(),
+        ));
+    }
+    let (udt_name, _, type_) = PG_SCALAR_TYPES
+        .iter()
+        .find(|(_, name, _)| *name == type_name)
+        .ok_or_else(|| anyhow!("unknown type {type_name} from pg row, may not supported yet."))?;
+    Ok((type_.clone(), scalar_extra(udt_name)))
+}
+
+// byte length of integers, binary copy sends int2 and oid in their own width.
+fn scalar_extra(udt_name: &str) -> HashMap<String, String> {
+    match udt_name {
+        "int2" => HashMap::from([("length".to_string(), "2".to_string())]),
+        "int4" => HashMap::from([("length".to_string(), "4".to_string())]),
+        "int8" => HashMap::from([("length".to_string(), "8".to_string())]),
+        "oid" => HashMap::from([("length".to_string(), "4".to_string())]),
+        _ => HashMap::new(),
     }
 }
 
@@ -42,7 +272,23 @@ pub fn parse_row_schema(row: &PgRow) -> Result<data_storages::Schema> {
                 let type_info = column.type_info();
                 let type_str = type_info.to_string();
                 let column_name = column.name();
-                let (type_, extra) = parse_pg_type(&type_str)?;
+                let (type_, extra) = match type_str.as_str() {
+                    // precision and scale are not in row metadata, neither the fields of
+                    // composite, take them from the value.
+                    "NUMERIC" | "NUMERIC[]" => {
+                        let type_ =
+                            parse_col_to_typed_value(&type_str, column_name, row)?.schema_type();
+                        let extra = type_.extra();
+                        (type_, extra)
+                    }
+                    _ if matches!(type_info.kind(), PgTypeKind::Composite(_)) => {
+                        let type_ =
+                            parse_col_to_typed_value(&type_str, column_name, row)?.schema_type();
+                        let extra = type_.extra();
+                        (type_, extra)
+                    }
+                    _ => parse_pg_type(&type_str)?,
+                };
                 Ok(data_storages::SchemaField {
                     name: column_name.to_string(),
                     type_,
@@ -53,46 +299,171 @@ pub fn parse_row_schema(row: &PgRow) -> Result<data_storages::Schema> {
     ))
 }
 
+// type of `udt_name` without type modifiers, arrays are prefixed with `_`.
+fn parse_udt_name(udt_name: &str) -> Result<data_storages::SchemaType> {
+    match udt_name {
+        // numeric without precision and scale can hold any value.
+        "numeric" => Ok(data_storages::SchemaType::Decimal(0, 0)),
+        array if array.starts_with('_') => Ok(data_storages::SchemaType::List(Box::new(
+            parse_udt_name(&array[1..])?,
+        ))),
+        udt_name => PG_SCALAR_TYPES
+            .iter()
+            .find(|(udt, _, _)| *udt == udt_name)
+            .map(|(_, _, type_)| type_.clone())
+            .ok_or_else(|| anyhow!("cannot parse type {udt_name}, may not supported yet.")),
+    }
+}
+
+/// column of table from `information_schema.columns`, or attribute of composite type from
+/// `information_schema.attributes` with the same column names.
 pub struct ColumnSchemaInDB {
     column_name: String,
+    data_type: String,
+    udt_schema: String,
     udt_name: String,
     is_nullable: Option<String>,
     character_maximum_length: Option<i32>,
+    numeric_precision: Option<i32>,
+    numeric_scale: Option<i32>,
 }
 
 impl From<PgRow> for ColumnSchemaInDB {
     fn from(value: PgRow) -> Self {
         ColumnSchemaInDB {
             column_name: value.get("column_name"),
+            data_type: value.get("data_type"),
+            udt_schema: value.get("udt_schema"),
             udt_name: value.get("udt_name"),
             is_nullable: value.get("is_nullable"),
             character_maximum_length: value.get("character_maximum_length"),
+            numeric_precision: value.get("numeric_precision"),
+            numeric_scale: value.get("numeric_scale"),
         }
     }
 }
 
 impl ColumnSchemaInDB {
-    pub fn to_data_schema(&self) -> Result<data_storages::SchemaField> {
-        let mut extra: HashMap<String, String> =
-            HashMap::from([("pg_type".to_string(), self.udt_name.clone())]);
+    pub fn name(&self) -> &str {
+        self.column_name.as_str()
+    }
+
+    /// schema and name of the composite type of column, whose fields must be given to
+    /// `to_data_schema`.
+    pub fn composite_type(&self) -> Option<(&str, &str)> {
+        (self.data_type == "USER-DEFINED")
+            .then_some((self.udt_schema.as_str(), self.udt_name.as_str()))
+    }
+
+    pub fn to_data_schema(
+        &self,
+        composite_fields: Option<Vec<data_storages::SchemaField>>,
+    ) -> Result<data_storages::SchemaField> {
+        let mut extra: HashMap<String, String> = scalar_extra(&self.udt_name);
+        extra.insert("pg_type".to_string(), self.udt_name.clone());
         if let Some(nullable) = &self.is_nullable {
             extra.insert("nullable".to_string(), utils::bool_str(nullable == "YES"));
         }
         if let Some(length) = self.character_maximum_length {
             extra.insert("length".to_string(), length.to_string());
         }
-        match self.udt_name.as_str() {
-            "varchar" => Ok(data_storages::SchemaField {
-                name: self.column_name.clone(),
-                type_: data_storages::SchemaType::String,
-                extra,
-            }),
-            "int4" => Ok(data_storages::SchemaField {
-                name: self.column_name.clone(),
-                type_: data_storages::SchemaType::Int32,
-                extra,
-            }),
-            unk => Err(anyhow!("cannot parse type {unk}, may not supported yet.")),
+        let type_ = match (
+            self.udt_name.as_str(),
+            self.numeric_precision,
+            self.numeric_scale,
+            composite_fields,
+        ) {
+            (_, _, _, Some(fields)) => data_storages::SchemaType::Struct(fields),
+            ("numeric", Some(precision), Some(scale), None) => {
+                data_storages::SchemaType::Decimal(u16::try_from(precision)?, i16::try_from(scale)?)
+            }
+            (udt_name, _, _, None) => parse_udt_name(udt_name)?,
+        };
+        extra.extend(type_.extra());
+        Ok(data_storages::SchemaField {
+            name: self.column_name.clone(),
+            type_,
+            extra,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::data_storages::{CoercionPolicy, SchemaType, SchemaTypeWithValue};
+
+    fn numeric_column(
+        udt_name: &str,
+        precision: Option<i32>,
+        scale: Option<i32>,
+    ) -> ColumnSchemaInDB {
+        ColumnSchemaInDB {
+            column_name: "n".to_string(),
+            data_type: "numeric".to_string(),
+            udt_schema: "pg_catalog".to_string(),
+            udt_name: udt_name.to_string(),
+            is_nullable: None,
+            character_maximum_length: None,
+            numeric_precision: precision,
+            numeric_scale: scale,
+        }
+    }
+
+    #[test]
+    fn numeric_schema_matches_values() {
+        // values of numeric columns are decoded as Decimal, whatever the type modifiers are.
+        let decimal = SchemaTypeWithValue::Decimal("12345678901234567890.125".parse().unwrap());
+        for (column, value) in [
+            (numeric_column("numeric", None, None), decimal.clone()),
+            (
+                numeric_column("numeric", Some(10), Some(2)),
+                SchemaTypeWithValue::Decimal("12.5".parse().unwrap()),
+            ),
+            (
+                numeric_column("_numeric", None, None),
+                SchemaTypeWithValue::List(vec![decimal.clone(), SchemaTypeWithValue::None]),
+            ),
+        ] {
+            let field = column.to_data_schema(None).unwrap();
+            assert!(
+                matches!(
+                    (&field.type_, &value),
+                    (SchemaType::Decimal(..), SchemaTypeWithValue::Decimal(_))
+                        | (SchemaType::List(_), SchemaTypeWithValue::List(_))
+                ),
+                "{:?} of {}",
+                field.type_,
+                column.udt_name
+            );
+            assert_eq!(
+                value.coerce(&field.type_, CoercionPolicy::Strict),
+                Some((value.clone(), 0)),
+                "{}",
+                column.udt_name
+            );
+        }
+    }
+
+    #[test]
+    fn parse_postgres_style_interval() {
+        // texts are printed by postgres in the `postgres` interval style.
+        for (text, months, days, microseconds) in [
+            ("1 year 2 mons -3 days +04:05:06.5", 14, -3, 14_706_500_000),
+            ("-04:05:06.000001", 0, 0, -14_706_000_001),
+            ("00:00:00", 0, 0, 0),
+            ("-1 years -2 mons", -14, 0, 0),
+            ("1 day -00:00:00.5", 0, 1, -500_000),
+        ] {
+            let expected = Interval {
+                months,
+                days,
+                microseconds,
+            };
+            assert_eq!(parse_pg_interval(text).unwrap(), expected, "{text}");
+        }
+        for text in ["P1Y", "1 fortnight", "1", "01:02"] {
+            assert!(parse_pg_interval(text).is_err(), "{text}");
         }
     }
 }
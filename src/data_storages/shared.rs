@@ -0,0 +1,73 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+
+use super::data_storages::{DataStorage, ReadResult, Row, Schema, SchemaTypeWithValue};
+
+struct Shared {
+    storage: Box<dyn DataStorage + Send>,
+    // writers which have not closed yet.
+    open: usize,
+}
+
+/// storage shared by several writers, writes are serialized and the storage is closed by the
+/// last writer. It's used for sinks which cannot be written by several instances, e.g. files.
+#[derive(Clone)]
+pub struct SharedStorage(Arc<Mutex<Shared>>);
+
+impl SharedStorage {
+    /// `writers` handles of `storage`, each of them should be closed once.
+    pub fn split(storage: Box<dyn DataStorage + Send>, writers: u32) -> Vec<Self> {
+        let shared = SharedStorage(Arc::new(Mutex::new(Shared {
+            storage,
+            open: writers as usize,
+        })));
+        vec![shared; writers as usize]
+    }
+}
+
+#[async_trait]
+impl DataStorage for SharedStorage {
+    async fn read_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Schema> {
+        self.0.lock().await.storage.read_schema(options).await
+    }
+
+    async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult> {
+        self.0.lock().await.storage.read(options).await
+    }
+
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let mut shared = self.0.lock().await;
+        shared.storage.chunk_read(cursor, limit, options).await
+    }
+
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        self.0
+            .lock()
+            .await
+            .storage
+            .write(data, schema, options)
+            .await
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        let mut shared = self.0.lock().await;
+        shared.open = shared.open.saturating_sub(1);
+        if shared.open == 0 {
+            shared.storage.close().await
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use crate::data_storages::{
+    data_storages::{self, ReadResult, SchemaTypeWithValue},
+    error::ParameterError,
+    sqlite::parser::{parse_col_to_typed_value, schema_type_to_sqlite, ColumnSchemaInDB},
+    utils::{incremental_query, incremental_watermark, merge_schema, valid_symbol, valid_table},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use sqlx::{
+    error::Error as SqlXError,
+    query::Query,
+    sqlite::{Sqlite, SqliteArguments, SqliteConnectOptions, SqliteConnection, SqliteRow},
+    Column, ConnectOptions, Connection, Row,
+};
+use std::{collections::HashSet, str::FromStr};
+
+// alias of rowid selected by `chunk_read`, removed from the rows returned.
+const ROWID_COLUMN: &str = "datawhirr_rowid";
+// max number of parameters of a statement, SQLITE_MAX_VARIABLE_NUMBER of sqlite since 3.32.0.
+const MAX_VARIABLES: usize = 32766;
+
+pub struct SqliteStorage {
+    connection: SqliteConnection,
+    // tables written by this storage, which have been created if not exist.
+    created_tables: HashSet<String>,
+}
+
+impl SqliteStorage {
+    /// the database file will be created if it does not exist.
+    pub async fn new(uri: &str) -> Result<Self, SqlXError> {
+        Ok(SqliteStorage {
+            connection: SqliteConnectOptions::from_str(uri)?
+                .create_if_missing(true)
+                .connect()
+                .await?,
+            created_tables: HashSet::new(),
+        })
+    }
+
+    async fn fetch_rows(
+        &mut self,
+        sql: &str,
+        params: Vec<SchemaTypeWithValue>,
+    ) -> Result<(Vec<data_storages::Row>, Option<data_storages::Schema>)> {
+        let query = params.into_iter().fold(sqlx::query(sql), bind_value);
+        let mut rows = query.fetch(&mut self.connection);
+        let mut results: Vec<data_storages::Row> = Vec::new();
+        let mut schema: Option<data_storages::Schema> = None;
+        while let Some(row) = rows.try_next().await? {
+            let row = sqliterow_to_row(row)?;
+            if let Some(s) = schema {
+                schema = Some(merge_schema(&s, &row.schema()));
+            } else {
+                schema = Some(row.schema());
+            };
+            results.push(row)
+        }
+        Ok((results, schema))
+    }
+}
+
+fn bind_value<'q>(
+    query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    value: SchemaTypeWithValue,
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    match value {
+        SchemaTypeWithValue::String(v) => query.bind(v),
+        SchemaTypeWithValue::Int32(v) => query.bind(v),
+        SchemaTypeWithValue::Int64(v) => query.bind(v),
+        SchemaTypeWithValue::Binary(v) => query.bind(v),
+        SchemaTypeWithValue::Boolean(v) => query.bind(v),
+        SchemaTypeWithValue::Timestamp(v) => query.bind(v),
+        SchemaTypeWithValue::Timestamptz(v) => query.bind(v),
+        SchemaTypeWithValue::Date(v) => query.bind(v),
+        SchemaTypeWithValue::Time(v) => query.bind(v),
+        SchemaTypeWithValue::Interval(v) => query.bind(v.to_iso8601()),
+        SchemaTypeWithValue::Double(v) => query.bind(v),
+        SchemaTypeWithValue::Float(v) => query.bind(v),
+        // sqlite has no exact numeric storage class, keep decimal as text.
+        SchemaTypeWithValue::Decimal(v) => query.bind(v.to_string()),
+        SchemaTypeWithValue::Uuid(v) => query.bind(v.hyphenated().to_string()),
+        SchemaTypeWithValue::Json(v) => query.bind(v.to_string()),
+        v @ (SchemaTypeWithValue::List(_) | SchemaTypeWithValue::Struct(_)) => {
+            query.bind(v.to_json().to_string())
+        }
+        SchemaTypeWithValue::None => query.bind(Option::<String>::None),
+    }
+}
+
+// rows after the watermark bound as the parameter. Dates and timestamps are stored as text in
+// several formats(e.g. with ` ` or `T` between date and time), which are compared by julianday.
+fn incremental_condition(column: &str, watermark: &SchemaTypeWithValue) -> String {
+    match watermark {
+        SchemaTypeWithValue::Date(_)
+        | SchemaTypeWithValue::Timestamp(_)
+        | SchemaTypeWithValue::Timestamptz(_) => {
+            format!(r#"julianday("{column}") > julianday(?)"#)
+        }
+        _ => format!(r#""{column}" > ?"#),
+    }
+}
+
+fn sqliterow_to_row(row: SqliteRow) -> Result<data_storages::Row> {
+    Ok(data_storages::Row(
+        row.columns()
+            .iter()
+            .map(|column| {
+                Ok(data_storages::Column {
+                    name: column.name().to_string(),
+                    value: parse_col_to_typed_value(&row, column.ordinal())?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?,
+    ))
+}
+
+#[async_trait]
+impl data_storages::DataStorage for SqliteStorage {
+    async fn read_schema(
+        &mut self,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<data_storages::Schema> {
+        if let Some(table) = options.get("table") {
+            valid_table(table)?;
+            let sql = r#"SELECT name, type, "notnull", pk FROM pragma_table_info(?) ORDER BY cid"#;
+            let mut rows = sqlx::query(sql).bind(table).fetch(&mut self.connection);
+            let mut results: Vec<data_storages::SchemaField> = Vec::new();
+            while let Some(row) = rows.try_next().await? {
+                results.push(ColumnSchemaInDB::from(row).to_data_schema())
+            }
+            if results.is_empty() {
+                return Err(ParameterError::new("cannot find `table` in database").into());
+            }
+            Ok(data_storages::Schema(results))
+        } else {
+            Err(ParameterError::new("cannot find `table` in options").into())
+        }
+    }
+
+    async fn read(
+        &mut self,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let (query, watermark) = incremental_query(options, incremental_condition)?;
+        let (data, schema) = self
+            .fetch_rows(query.as_str(), watermark.into_iter().collect())
+            .await?;
+        Ok(ReadResult {
+            data,
+            schema: schema.unwrap_or(data_storages::Schema(vec![])),
+            cursor: None,
+        })
+    }
+
+    /// page by rowid of `table`, or by `pk` in options if given. The cursor is the rowid(or `pk`
+    /// value) of last row.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        let (sql, pk, params) = match (options.get("pk"), options.get("table")) {
+            (Some(pk), _) => {
+                valid_symbol(pk)?;
+                let (query, watermark) = incremental_query(options, incremental_condition)?;
+                let condition = match cursor {
+                    Some(_) => format!(r#"where "{pk}" > ?"#),
+                    None => String::new(),
+                };
+                (
+                    format!(
+                        r#"select * from ({query}) as datawhirr_chunk {condition} order by "{pk}" asc limit {limit}"#
+                    ),
+                    pk.to_string(),
+                    // the watermark is in the inner query, before the cursor.
+                    watermark.into_iter().chain(cursor).collect::<Vec<_>>(),
+                )
+            }
+            (None, Some(table)) => {
+                valid_table(table)?;
+                let watermark = incremental_watermark(options)?;
+                let conditions = cursor
+                    .as_ref()
+                    .map(|_| "rowid > ?".to_string())
+                    .into_iter()
+                    .chain(
+                        watermark
+                            .as_ref()
+                            .map(|(column, value)| incremental_condition(column, value)),
+                    )
+                    .collect::<Vec<_>>();
+                let condition = if conditions.is_empty() {
+                    String::new()
+                } else {
+                    format!("where {}", conditions.join(" and "))
+                };
+                (
+                    format!(
+                        "select rowid as {ROWID_COLUMN}, * from {table} {condition} order by rowid asc limit {limit}"
+                    ),
+                    ROWID_COLUMN.to_string(),
+                    cursor
+                        .into_iter()
+                        .chain(watermark.map(|(_, value)| value))
+                        .collect(),
+                )
+            }
+            (None, None) => {
+                return Err(ParameterError::new(
+                    "cannot find required options `table` or `pk` on chunk_read",
+                )
+                .into())
+            }
+        };
+        let (mut data, schema) = self.fetch_rows(sql.as_str(), params).await?;
+        let cursor = data
+            .last()
+            .map(|row| {
+                row.get(pk.as_str()).cloned().ok_or(ParameterError::new(
+                    "cannot find `pk` column in the result of query",
+                ))
+            })
+            .transpose()?;
+        let mut schema = schema.unwrap_or(data_storages::Schema(vec![]));
+        if pk == ROWID_COLUMN {
+            for row in data.iter_mut() {
+                row.0.retain(|column| column.name != ROWID_COLUMN);
+            }
+            schema.0.retain(|field| field.name != ROWID_COLUMN);
+        }
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor,
+        })
+    }
+
+    fn incremental(&self) -> bool {
+        true
+    }
+
+    fn concurrent_write(&self) -> bool {
+        true
+    }
+
+    fn resumable(&self) -> bool {
+        true
+    }
+
+    /// insert rows in one transaction, the table will be created by schema if it is missing.
+    async fn write(
+        &mut self,
+        data: Vec<data_storages::Row>,
+        schema: Option<data_storages::Schema>,
+        options: &std::collections::HashMap<&str, &str>,
+    ) -> Result<()> {
+        let table = options
+            .get("table")
+            .ok_or(ParameterError::new("cannot find `table` in options"))?;
+        valid_table(table)?;
+        let schema = match (schema, data.first()) {
+            (_, None) => return Ok(()),
+            (Some(schema), _) => schema,
+            (None, Some(row)) => row.schema(),
+        };
+        if schema.0.is_empty() {
+            return Err(ParameterError::new("cannot write rows without any column").into());
+        }
+        for field in &schema.0 {
+            valid_symbol(field.name.as_str())?;
+        }
+        if !self.created_tables.contains(*table) {
+            let definitions = schema
+                .0
+                .iter()
+                .map(|field| {
+                    format!(
+                        r#""{}" {}"#,
+                        field.name,
+                        schema_type_to_sqlite(&field.type_)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            sqlx::query(format!("create table if not exists {table} ({definitions})").as_str())
+                .execute(&mut self.connection)
+                .await?;
+            self.created_tables.insert(table.to_string());
+        }
+        let columns = schema
+            .0
+            .iter()
+            .map(|field| format!(r#""{}""#, field.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let placeholders = format!("({})", vec!["?"; schema.0.len()].join(", "));
+        // rows are inserted by statements of as many rows as the parameters allow.
+        let rows_per_insert = (MAX_VARIABLES / schema.0.len()).max(1);
+        let mut tx = self.connection.begin().await?;
+        for rows in data.chunks(rows_per_insert) {
+            let sql = format!(
+                "insert into {table} ({columns}) values {}",
+                vec![placeholders.as_str(); rows.len()].join(", ")
+            );
+            let mut query = sqlx::query(sql.as_str());
+            for row in rows {
+                for field in &schema.0 {
+                    let value = row
+                        .get(&field.name)
+                        .cloned()
+                        .unwrap_or(SchemaTypeWithValue::None);
+                    query = bind_value(query, value);
+                }
+            }
+            query.execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_storages::data_storages::{
+        Column, DataStorage, Row, Schema, SchemaField, SchemaType,
+    };
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn uuid_is_read_as_declared() {
+        let mut storage = SqliteStorage::new("sqlite::memory:").await.unwrap();
+        let id = uuid::Uuid::from_u128(0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8);
+        let row = Row(vec![
+            Column {
+                name: "id".to_string(),
+                value: SchemaTypeWithValue::Uuid(id),
+            },
+            Column {
+                name: "n".to_string(),
+                value: SchemaTypeWithValue::None,
+            },
+        ]);
+        let schema = Schema(vec![
+            SchemaField {
+                name: "id".to_string(),
+                type_: SchemaType::Uuid,
+                extra: HashMap::new(),
+            },
+            SchemaField {
+                name: "n".to_string(),
+                type_: SchemaType::Int64,
+                extra: HashMap::new(),
+            },
+        ]);
+        let options = HashMap::from([("table", "t")]);
+        storage
+            .write(vec![row], Some(schema), &options)
+            .await
+            .unwrap();
+        let declared = storage.read_schema(&options).await.unwrap();
+        let result = storage.read(&options).await.unwrap();
+        assert_eq!(declared.0[0].type_, SchemaType::String);
+        assert_eq!(
+            result.data[0].get("id"),
+            Some(&SchemaTypeWithValue::String(id.to_string()))
+        );
+        assert_eq!(result.data[0].get("n"), Some(&SchemaTypeWithValue::None));
+    }
+
+    #[tokio::test]
+    async fn numeric_affinity_is_read_as_decimal() {
+        let mut storage = SqliteStorage::new("sqlite::memory:").await.unwrap();
+        sqlx::query("create table t (price decimal(10, 2), amount numeric, ratio real)")
+            .execute(&mut storage.connection)
+            .await
+            .unwrap();
+        let options = HashMap::from([("table", "t")]);
+        let schema = storage.read_schema(&options).await.unwrap();
+        let types = schema.0.iter().map(|f| f.type_.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                SchemaType::Decimal(10, 2),
+                SchemaType::Decimal(0, 0),
+                SchemaType::Double
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_beyond_variable_limit_are_written() {
+        let mut storage = SqliteStorage::new("sqlite::memory:").await.unwrap();
+        let schema = Schema(vec![SchemaField {
+            name: "n".to_string(),
+            type_: SchemaType::Int64,
+            extra: HashMap::new(),
+        }]);
+        let rows = (0..MAX_VARIABLES as i64 + 10)
+            .map(|n| {
+                Row(vec![Column {
+                    name: "n".to_string(),
+                    value: SchemaTypeWithValue::Int64(n),
+                }])
+            })
+            .collect::<Vec<_>>();
+        let options = HashMap::from([("table", "t")]);
+        for _ in 0..2 {
+            storage
+                .write(rows.clone(), Some(schema.clone()), &options)
+                .await
+                .unwrap();
+        }
+        let (count, sum): (i64, i64) = sqlx::query_as("select count(*), sum(n) from t")
+            .fetch_one(&mut storage.connection)
+            .await
+            .unwrap();
+        let n = MAX_VARIABLES as i64 + 10;
+        assert_eq!((count, sum), (2 * n, n * (n - 1)));
+        assert!(storage.created_tables.contains("t"));
+    }
+}
@@ -0,0 +1,3 @@
+mod lite;
+mod parser;
+pub use lite::SqliteStorage;
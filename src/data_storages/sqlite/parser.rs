@@ -0,0 +1,149 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use std::collections::HashMap;
+
+use crate::data_storages::{
+    data_storages::{self, TimePrecision},
+    utils,
+};
+use sqlx::sqlite::{Sqlite, SqliteRow};
+use sqlx::{Column, Decode, Row, Type, TypeInfo, ValueRef};
+
+fn get_value<'r, T>(
+    row: &'r SqliteRow,
+    index: usize,
+    to_value: impl FnOnce(T) -> Result<data_storages::SchemaTypeWithValue>,
+) -> Result<data_storages::SchemaTypeWithValue>
+where
+    T: Decode<'r, Sqlite> + Type<Sqlite>,
+{
+    match row.try_get::<Option<T>, _>(index)? {
+        Some(value) => to_value(value),
+        None => Ok(data_storages::SchemaTypeWithValue::None),
+    }
+}
+
+/// sqlite is dynamic typed, value is parsed by its storage class unless the column is declared
+/// as boolean, date, time or datetime.
+pub fn parse_col_to_typed_value(
+    row: &SqliteRow,
+    index: usize,
+) -> Result<data_storages::SchemaTypeWithValue> {
+    use data_storages::SchemaTypeWithValue as V;
+    let raw = row.try_get_raw(index)?;
+    if raw.is_null() {
+        return Ok(V::None);
+    }
+    let storage_class = raw.type_info().name().to_string();
+    match (row.column(index).type_info().name(), storage_class.as_str()) {
+        ("BOOLEAN", "INTEGER") => get_value(row, index, |v: bool| Ok(V::Boolean(v))),
+        ("DATE", "TEXT") => get_value(row, index, |v: NaiveDate| Ok(V::Date(v))),
+        ("TIME", "TEXT") => get_value(row, index, |v: NaiveTime| Ok(V::Time(v))),
+        // sqlite has no time zone, datetime is taken as UTC.
+        ("DATETIME", "TEXT" | "INTEGER" | "REAL") => {
+            get_value(row, index, |v: DateTime<Utc>| Ok(V::Timestamptz(v)))
+        }
+        (_, "INTEGER") => get_value(row, index, |v: i64| Ok(V::Int64(v))),
+        (_, "REAL") => get_value(row, index, |v: f64| Ok(V::Double(v))),
+        (_, "TEXT") => get_value(row, index, |v: String| Ok(V::String(v))),
+        (_, "BLOB") => get_value(row, index, |v: Vec<u8>| Ok(V::Binary(v))),
+        (_, unk) => Err(anyhow!(
+            "cannot parse storage class {unk}, may not supported yet."
+        )),
+    }
+}
+
+/// type by the affinity rules of declared type, see https://www.sqlite.org/datatype3.html
+fn parse_sqlite_type(declared_type: &str) -> data_storages::SchemaType {
+    use data_storages::SchemaType as T;
+    let declared_type = declared_type.to_uppercase();
+    match declared_type.as_str() {
+        "BOOL" | "BOOLEAN" => T::Boolean,
+        "DATE" => T::Date,
+        "TIME" => T::Time,
+        "DATETIME" | "TIMESTAMP" => T::Timestamptz(TimePrecision::Micro),
+        // sqlite has no uuid type and rows do not report the declared type, uuids are read as text.
+        "UUID" => T::String,
+        t if t.contains("INT") => T::Int64,
+        t if t.contains("CHAR") || t.contains("CLOB") || t.contains("TEXT") => T::String,
+        t if t.is_empty() || t.contains("BLOB") => T::Binary,
+        t if t.contains("REAL") || t.contains("FLOA") || t.contains("DOUB") => T::Double,
+        // numeric affinity, e.g. `DECIMAL(10, 2)`, whose values must not be rounded as doubles.
+        t => {
+            let modifiers = t
+                .split_once('(')
+                .and_then(|(_, modifiers)| modifiers.strip_suffix(')'))
+                .map(|modifiers| {
+                    modifiers
+                        .split(',')
+                        .map(|modifier| modifier.trim().parse::<u16>().ok())
+                        .collect::<Vec<_>>()
+                });
+            match modifiers.as_deref() {
+                Some([Some(precision)]) => T::Decimal(*precision, 0),
+                Some([Some(precision), Some(scale)]) => {
+                    T::Decimal(*precision, i16::try_from(*scale).unwrap_or(i16::MAX))
+                }
+                _ => T::Decimal(0, 0),
+            }
+        }
+    }
+}
+
+pub fn schema_type_to_sqlite(type_: &data_storages::SchemaType) -> &'static str {
+    use data_storages::SchemaType as T;
+    match type_ {
+        T::String
+        | T::Decimal(..)
+        | T::Json
+        | T::Interval
+        | T::Uuid
+        | T::List(_)
+        | T::Struct(_) => "TEXT",
+        T::Int32 | T::Int64 => "INTEGER",
+        T::Binary => "BLOB",
+        T::Boolean => "BOOLEAN",
+        T::Timestamp(_) | T::Timestamptz(_) => "DATETIME",
+        T::Date => "DATE",
+        T::Time => "TIME",
+        T::Double | T::Float => "REAL",
+        T::None => "",
+    }
+}
+
+pub struct ColumnSchemaInDB {
+    name: String,
+    type_: String,
+    notnull: bool,
+    pk: i64,
+}
+
+impl From<SqliteRow> for ColumnSchemaInDB {
+    fn from(value: SqliteRow) -> Self {
+        ColumnSchemaInDB {
+            name: value.get("name"),
+            type_: value.get("type"),
+            notnull: value.get("notnull"),
+            pk: value.get("pk"),
+        }
+    }
+}
+
+impl ColumnSchemaInDB {
+    pub fn to_data_schema(&self) -> data_storages::SchemaField {
+        let mut extra: HashMap<String, String> = HashMap::from([
+            ("sqlite_type".to_string(), self.type_.clone()),
+            ("nullable".to_string(), utils::bool_str(!self.notnull)),
+        ]);
+        if self.pk > 0 {
+            extra.insert("pk".to_string(), self.pk.to_string());
+        }
+        let type_ = parse_sqlite_type(&self.type_);
+        extra.extend(type_.extra());
+        data_storages::SchemaField {
+            name: self.name.clone(),
+            type_,
+            extra,
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use super::{
+    csv::{column_names, names_to_schema, parse_csv_options},
+    data_storages::{BinaryEncoding, ReadResult, Row, Schema, SchemaTypeWithValue},
+    error::ParameterError,
+    infer::{apply_schema, infer_schema, InferOptions},
+    jsonl::{next_row, row_to_line, rows_schema},
+    utils::{blocking, option_parse},
+    DataStorage,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+};
+
+// lines sampled by `read_schema` of jsonl by default.
+const DEFAULT_SAMPLE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+enum StdioFormat {
+    Csv,
+    Jsonl,
+}
+
+impl StdioFormat {
+    fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Ok(StdioFormat::Csv),
+            "jsonl" => Ok(StdioFormat::Jsonl),
+            unk => Err(ParameterError::new(
+                format!("unsupported stdio format `{unk}`, must be `csv` or `jsonl`").as_str(),
+            )
+            .into()),
+        }
+    }
+}
+
+// stdin can only be read once, so the reader is kept for the whole transfer.
+enum StdinReader {
+    Csv(csv::Reader<io::Stdin>, Vec<String>),
+    Jsonl(Lines<BufReader<io::Stdin>>),
+}
+
+enum StdoutWriter {
+    Csv(Box<csv::Writer<io::Stdout>>),
+    Jsonl(BufWriter<io::Stdout>),
+}
+
+pub struct StdioDataStorage {
+    // format of `stdio+{format}://`, or `format` in options(csv by default) for `stdio://`.
+    format: Option<StdioFormat>,
+    reader: Option<StdinReader>,
+    // rows sampled by `read_schema`, they are returned by the following reads.
+    pending: VecDeque<Row>,
+    // number of rows have been returned.
+    consumed: i64,
+    // schema of csv inferred on first read if `infer_schema=true`, shared by later reads.
+    inferred: Option<Schema>,
+    writer: Option<StdoutWriter>,
+}
+
+impl StdioDataStorage {
+    /// uri in form of `stdio://`, `stdio+csv://` or `stdio+jsonl://`.
+    pub fn new(uri: &str) -> Result<Self> {
+        let format = match uri
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_lowercase())
+        {
+            Some(scheme) if scheme == "stdio" => None,
+            Some(scheme) if scheme.starts_with("stdio+") => {
+                Some(StdioFormat::from_name(&scheme["stdio+".len()..])?)
+            }
+            _ => {
+                return Err(ParameterError::new(
+                    format!("invalid stdio uri `{uri}`, must be `stdio+{{format}}://`").as_str(),
+                )
+                .into())
+            }
+        };
+        Ok(StdioDataStorage {
+            format,
+            reader: None,
+            pending: VecDeque::new(),
+            consumed: 0,
+            inferred: None,
+            writer: None,
+        })
+    }
+
+    fn format(&self, options: &HashMap<&str, &str>) -> Result<StdioFormat> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => StdioFormat::from_name(options.get("format").unwrap_or(&"csv")),
+        }
+    }
+
+    // at most `limit` rows read from stdin on the blocking threads, the reader is taken and
+    // given back, it's created on first read.
+    async fn read_stdin(
+        &mut self,
+        limit: usize,
+        options: &HashMap<&str, &str>,
+    ) -> Result<Vec<Row>> {
+        let format = self.format(options)?;
+        let csv_options = parse_csv_options(options)?;
+        let reader = self.reader.take();
+        let (reader, data) = blocking(move || {
+            let mut reader = match reader {
+                Some(reader) => reader,
+                None => match format {
+                    StdioFormat::Csv => {
+                        let mut reader = csv_options.reader(io::stdin());
+                        let names = column_names(&mut reader, csv_options.header)?;
+                        StdinReader::Csv(reader, names)
+                    }
+                    StdioFormat::Jsonl => StdinReader::Jsonl(BufReader::new(io::stdin()).lines()),
+                },
+            };
+            let mut data: Vec<Row> = Vec::new();
+            match &mut reader {
+                StdinReader::Csv(reader, names) => {
+                    let mut record = csv::StringRecord::new();
+                    while data.len() < limit && reader.read_record(&mut record)? {
+                        data.push(csv_options.record_to_row(&record, names));
+                    }
+                }
+                StdinReader::Jsonl(lines) => {
+                    while data.len() < limit {
+                        match next_row(lines)? {
+                            Some(row) => data.push(row),
+                            None => break,
+                        }
+                    }
+                }
+            }
+            Ok((reader, data))
+        })
+        .await?;
+        self.reader = Some(reader);
+        Ok(data)
+    }
+
+    // schema of csv inferred from the first `sample_size` records if `infer_schema=true`, as
+    // `file+csv` does. The sampled records are kept to be returned by the following reads.
+    async fn inferred_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Option<Schema>> {
+        let infer_options = InferOptions::from_options(options)?;
+        if !infer_options.enabled || !matches!(self.format(options)?, StdioFormat::Csv) {
+            return Ok(None);
+        }
+        if self.inferred.is_none() {
+            let sample = self.read_stdin(infer_options.sample_size, options).await?;
+            self.inferred = Some(match (infer_schema(&sample, true), &self.reader) {
+                (Some(schema), _) => schema,
+                (None, Some(StdinReader::Csv(_, names))) => names_to_schema(names),
+                (None, _) => Schema(vec![]),
+            });
+            self.pending.extend(sample);
+        }
+        Ok(self.inferred.clone())
+    }
+
+    // next at most `limit` rows and their schema, sampled rows are taken first.
+    async fn next_rows(
+        &mut self,
+        limit: usize,
+        options: &HashMap<&str, &str>,
+    ) -> Result<(Vec<Row>, Schema)> {
+        let inferred = self.inferred_schema(options).await?;
+        let mut data: Vec<Row> = Vec::new();
+        while data.len() < limit {
+            match self.pending.pop_front() {
+                Some(row) => data.push(row),
+                None => break,
+            }
+        }
+        data.extend(self.read_stdin(limit - data.len(), options).await?);
+        match (inferred, &self.reader) {
+            (Some(schema), _) => Ok((
+                apply_schema(data, &schema, usize::try_from(self.consumed)?)?,
+                schema,
+            )),
+            (None, Some(StdinReader::Csv(_, names))) => {
+                let schema = names_to_schema(names);
+                Ok((data, schema))
+            }
+            (None, _) => {
+                let schema = rows_schema(&data).unwrap_or(Schema(vec![]));
+                Ok((data, schema))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataStorage for StdioDataStorage {
+    /// header of csv, or schema inferred from the first `sample_size` records of csv if
+    /// `infer_schema=true` or merged from the first `sample_size` lines of jsonl.
+    async fn read_schema(&mut self, options: &HashMap<&str, &str>) -> Result<Schema> {
+        if let Some(schema) = self.inferred_schema(options).await? {
+            return Ok(schema);
+        }
+        let sample_size = match self.format(options)? {
+            StdioFormat::Csv => 0,
+            StdioFormat::Jsonl => option_parse(options, "sample_size", DEFAULT_SAMPLE_SIZE)?,
+        };
+        let (sample, schema) = self.next_rows(sample_size, options).await?;
+        for row in sample.into_iter().rev() {
+            self.pending.push_front(row);
+        }
+        if schema.0.is_empty() {
+            return Err(ParameterError::new("cannot infer schema from empty stdin").into());
+        }
+        Ok(schema)
+    }
+
+    async fn read(&mut self, options: &HashMap<&str, &str>) -> Result<ReadResult> {
+        let (data, schema) = self.next_rows(usize::MAX, options).await?;
+        self.consumed += i64::try_from(data.len())?;
+        Ok(ReadResult {
+            data,
+            schema,
+            cursor: None,
+        })
+    }
+
+    /// cursor is the number of rows has been read, stdin can not be rewound so only the cursor
+    /// returned by last call is accepted.
+    async fn chunk_read(
+        &mut self,
+        cursor: Option<SchemaTypeWithValue>,
+        limit: u32,
+        options: &HashMap<&str, &str>,
+    ) -> Result<ReadResult> {
+        match cursor {
+            None if self.consumed == 0 => {}
+            Some(SchemaTypeWithValue::Int64(offset)) if offset == self.consumed => {}
+            unk => {
+                return Err(ParameterError::new(
+                    format!("invalid cursor {unk:?} for stdin, must be returned by last read")
+                        .as_str(),
+                )
+                .into())
+            }
+        }
+        let (data, schema) = self.next_rows(limit as usize, options).await?;
+        self.consumed += i64::try_from(data.len())?;
+        Ok(ReadResult {
+            cursor: if data.is_empty() {
+                None
+            } else {
+                Some(SchemaTypeWithValue::Int64(self.consumed))
+            },
+            data,
+            schema,
+        })
+    }
+
+    /// write rows to stdout, the header of csv is written on the first write.
+    async fn write(
+        &mut self,
+        data: Vec<Row>,
+        schema: Option<Schema>,
+        options: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        let csv_options = parse_csv_options(options)?;
+        let schema = match (schema, data.first()) {
+            (Some(schema), _) => schema,
+            (None, Some(row)) => row.schema(),
+            (None, None) => return Ok(()),
+        };
+        let format = self.format(options)?;
+        let binary_encoding = BinaryEncoding::from_options(options)?;
+        // written on the blocking threads, the writer is taken and given back.
+        let writer = self.writer.take();
+        self.writer = Some(
+            blocking(move || {
+                let mut writer = match writer {
+                    Some(writer) => writer,
+                    None => match format {
+                        StdioFormat::Csv => {
+                            let mut writer = csv_options.writer(io::stdout());
+                            if csv_options.header {
+                                csv_options.write_header(&mut writer, &schema)?;
+                            }
+                            StdoutWriter::Csv(Box::new(writer))
+                        }
+                        StdioFormat::Jsonl => StdoutWriter::Jsonl(BufWriter::new(io::stdout())),
+                    },
+                };
+                match &mut writer {
+                    StdoutWriter::Csv(writer) => csv_options.write_rows(writer, &data, &schema)?,
+                    StdoutWriter::Jsonl(writer) => {
+                        let schema = Some(schema);
+                        for row in &data {
+                            writer.write_all(
+                                row_to_line(row, &schema, binary_encoding)?.as_bytes(),
+                            )?;
+                            writer.write_all(b"\n")?;
+                        }
+                        writer.flush()?;
+                    }
+                }
+                Ok(writer)
+            })
+            .await?,
+        );
+        Ok(())
+    }
+}
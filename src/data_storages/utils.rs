@@ -0,0 +1,522 @@
+use anyhow::Result;
+use bigdecimal::{num_bigint::BigInt, BigDecimal, ToPrimitive};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use core::panic;
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    mem, path,
+    sync::LazyLock,
+};
+
+use super::{
+    data_storages::{Schema, SchemaField, SchemaType, SchemaTypeWithValue},
+    error::ParameterError,
+    infer::infer_text_type,
+};
+
+// extract file path from `{scheme}:///absoult/path` or `{scheme}:///$PWD/relative/path`
+pub fn extract_file_uri_path(uri: &str, scheme: &str) -> String {
+    let prefix = format!("{scheme}://");
+    if uri.starts_with(format!("{prefix}/").as_str()) {
+        uri.replacen(prefix.as_str(), "", 1)
+            .replace("$PWD", path::absolute(".").unwrap().to_str().unwrap())
+    } else {
+        panic!("not support auth/host uri, only `{scheme}:///absoult/path` or `{scheme}:///$PWD/relative/path` has been supported")
+    }
+}
+
+pub fn option_bool(options: &HashMap<&str, &str>, key: &str, default: bool) -> Result<bool> {
+    match options.get(key) {
+        None => Ok(default),
+        Some(value) => match value.to_lowercase().as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(ParameterError::new(
+                format!("`{key}` must be `true` or `false`, got `{value}`").as_str(),
+            )
+            .into()),
+        },
+    }
+}
+
+/// run `f` of blocking io, e.g. kafka client or stdio, on the blocking threads not to stall the
+/// workers of runtime.
+pub async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(f).await?
+}
+
+pub fn option_parse<T: std::str::FromStr>(
+    options: &HashMap<&str, &str>,
+    key: &str,
+    default: T,
+) -> Result<T> {
+    match options.get(key) {
+        None => Ok(default),
+        Some(value) => value.parse::<T>().map_err(|_| {
+            ParameterError::new(format!("invalid value `{value}` of `{key}`").as_str()).into()
+        }),
+    }
+}
+
+fn schema_fieldname_to_field(schema: &Schema) -> HashMap<&str, SchemaField> {
+    schema
+        .0
+        .iter()
+        .map(|schema| (schema.name.as_str(), schema.clone()))
+        .collect::<_>()
+}
+
+fn schema_get_fieldnames(schema: &Schema) -> HashSet<&str> {
+    schema
+        .0
+        .iter()
+        .map(|schema| schema.name.as_str())
+        .collect::<HashSet<_>>()
+}
+
+/// type holds values of both, integers widen to Int64 then Double and dates to timestamps. Types
+/// which are not compatible are merged to String.
+pub fn merge_type(type1: &SchemaType, type2: &SchemaType) -> SchemaType {
+    match (type1, type2) {
+        (type1, SchemaType::None) => type1.clone(),
+        (SchemaType::None, type2) => type2.clone(),
+        (SchemaType::Int32, SchemaType::Int64) | (SchemaType::Int64, SchemaType::Int32) => {
+            SchemaType::Int64
+        }
+        (
+            SchemaType::Int32 | SchemaType::Int64 | SchemaType::Float | SchemaType::Double,
+            SchemaType::Float | SchemaType::Double,
+        )
+        | (SchemaType::Float | SchemaType::Double, SchemaType::Int32 | SchemaType::Int64)
+            if mem::discriminant(type1) != mem::discriminant(type2) =>
+        {
+            SchemaType::Double
+        }
+        // unbounded decimal holds any decimal.
+        (
+            SchemaType::Decimal(0, 0),
+            SchemaType::Int32 | SchemaType::Int64 | SchemaType::Decimal(..),
+        )
+        | (SchemaType::Int32 | SchemaType::Int64, SchemaType::Decimal(0, 0))
+        | (SchemaType::Decimal(..), SchemaType::Decimal(0, 0)) => SchemaType::Decimal(0, 0),
+        (SchemaType::Decimal(precision, scale), SchemaType::Int32)
+        | (SchemaType::Int32, SchemaType::Decimal(precision, scale)) => {
+            widen_decimal((*precision, *scale), (10, 0))
+        }
+        (SchemaType::Decimal(precision, scale), SchemaType::Int64)
+        | (SchemaType::Int64, SchemaType::Decimal(precision, scale)) => {
+            widen_decimal((*precision, *scale), (19, 0))
+        }
+        (SchemaType::Date, SchemaType::Timestamp(precision))
+        | (SchemaType::Timestamp(precision), SchemaType::Date) => SchemaType::Timestamp(*precision),
+        (SchemaType::Date, SchemaType::Timestamptz(precision))
+        | (SchemaType::Timestamptz(precision), SchemaType::Date) => {
+            SchemaType::Timestamptz(*precision)
+        }
+        (SchemaType::Decimal(precision1, scale1), SchemaType::Decimal(precision2, scale2)) => {
+            widen_decimal((*precision1, *scale1), (*precision2, *scale2))
+        }
+        (SchemaType::Timestamp(precision1), SchemaType::Timestamp(precision2)) => {
+            SchemaType::Timestamp(*precision1.max(precision2))
+        }
+        (SchemaType::Timestamptz(precision1), SchemaType::Timestamptz(precision2)) => {
+            SchemaType::Timestamptz(*precision1.max(precision2))
+        }
+        (SchemaType::List(inner1), SchemaType::List(inner2)) => {
+            SchemaType::List(Box::new(merge_type(inner1, inner2)))
+        }
+        (SchemaType::Struct(fields1), SchemaType::Struct(fields2)) => {
+            SchemaType::Struct(merge_schema(&Schema(fields1.clone()), &Schema(fields2.clone())).0)
+        }
+        (type1, type2) if mem::discriminant(type1) == mem::discriminant(type2) => type2.clone(),
+        _ => SchemaType::String,
+    }
+}
+
+// decimal holds values of both, with the max integer digits and the max scale.
+fn widen_decimal(decimal1: (u16, i16), decimal2: (u16, i16)) -> SchemaType {
+    let integer_digits = |(precision, scale): (u16, i16)| i32::from(precision) - i32::from(scale);
+    let scale = decimal1.1.max(decimal2.1);
+    let precision = integer_digits(decimal1).max(integer_digits(decimal2)) + i32::from(scale);
+    SchemaType::Decimal(u16::try_from(precision).unwrap_or(u16::MAX), scale)
+}
+
+pub fn merge_schema(schema1: &Schema, schema2: &Schema) -> Schema {
+    let schema2_name_to_schema = schema_fieldname_to_field(schema2);
+
+    let schema1_columns = schema_get_fieldnames(schema1);
+
+    let mut res: Vec<SchemaField> = vec![];
+    for schema1_col in &schema1.0 {
+        match schema2_name_to_schema.get(schema1_col.name.as_str()) {
+            // merge none and typed to nullable
+            Some(schema2_col) => match schema2_col.type_ {
+                SchemaType::None => match schema1_col.type_ {
+                    SchemaType::None => {
+                        let mut new_col = schema1_col.clone();
+                        new_col.extra.extend(schema2_col.extra.clone());
+                        res.push(new_col);
+                    }
+                    _ => {
+                        let mut new_col = schema1_col.clone();
+                        new_col.extra.extend(schema2_col.extra.clone());
+                        new_col
+                            .extra
+                            .insert("nullable".to_string(), "true".to_string());
+                        res.push(new_col);
+                    }
+                },
+                _ => {
+                    let mut new_col = schema2_col.clone();
+                    new_col.extra.extend(schema1_col.extra.clone());
+                    new_col.type_ = merge_type(&schema1_col.type_, &schema2_col.type_);
+                    new_col.extra.extend(new_col.type_.extra());
+                    if let SchemaType::None = schema1_col.type_ {
+                        new_col
+                            .extra
+                            .insert("nullable".to_string(), "true".to_string());
+                    }
+                    res.push(new_col);
+                }
+            },
+            None => res.push(schema1_col.clone()),
+        }
+    }
+    // more columns of schema2 follow in their order.
+    res.extend(
+        schema2
+            .0
+            .iter()
+            .filter(|schema2_col| !schema1_columns.contains(schema2_col.name.as_str()))
+            .cloned(),
+    );
+    Schema(res)
+}
+
+pub fn bool_str(b: bool) -> String {
+    if b { "true" } else { "false" }.to_string()
+}
+
+static SYMBOL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^[a-zA-Z_][a-zA-Z0-9_]{0,127}$").unwrap());
+
+pub fn valid_symbol(table_or_col_name: &str) -> Result<()> {
+    if SYMBOL.is_match(table_or_col_name) {
+        Ok(())
+    } else {
+        Err(ParameterError::new("invalid table or column name").into())
+    }
+}
+
+// table could be `table` or `schema.table`
+pub fn valid_table(table: &str) -> Result<()> {
+    table.split('.').try_for_each(valid_symbol)
+}
+
+// sql of `query` or `select * from {table}` in options
+pub fn query_from_options(options: &HashMap<&str, &str>) -> Result<String> {
+    if let Some(table) = options.get("table") {
+        valid_table(table)?;
+        Ok(format!("select * from {}", table))
+    } else {
+        Ok(options
+            .get("query")
+            .ok_or(ParameterError::new(
+                "cannot find any `query` or `table` in options",
+            ))?
+            .to_string())
+    }
+}
+
+/// `incremental_column` and typed `watermark` in options, None if they are not set. `watermark`
+/// is text of value of type `watermark_type`(schema type in json), or of type inferred from the
+/// text if `watermark_type` is not set.
+pub fn incremental_watermark(
+    options: &HashMap<&str, &str>,
+) -> Result<Option<(String, SchemaTypeWithValue)>> {
+    let (Some(column), Some(watermark)) =
+        (options.get("incremental_column"), options.get("watermark"))
+    else {
+        return Ok(None);
+    };
+    valid_symbol(column)?;
+    let type_ = match options.get("watermark_type") {
+        Some(type_) => serde_json::from_str(type_)
+            .map_err(|_| ParameterError::new("watermark_type is invalid."))?,
+        None => infer_text_type(watermark),
+    };
+    let value = SchemaTypeWithValue::String(watermark.to_string())
+        .cast(&type_)
+        .ok_or_else(|| ParameterError::new("watermark mismatches watermark_type."))?;
+    Ok(Some((column.to_string(), value)))
+}
+
+/// sql of `query_from_options` filtered by rows after `incremental_watermark`, and the watermark
+/// to bind. `condition` builds the filter from the column and the watermark, which is bound as
+/// the only parameter of the sql.
+pub fn incremental_query(
+    options: &HashMap<&str, &str>,
+    condition: impl Fn(&str, &SchemaTypeWithValue) -> String,
+) -> Result<(String, Option<SchemaTypeWithValue>)> {
+    let query = query_from_options(options)?;
+    Ok(match incremental_watermark(options)? {
+        Some((column, watermark)) => {
+            let condition = condition(&column, &watermark);
+            (
+                format!("select * from ({query}) as datawhirr_incremental where {condition}"),
+                Some(watermark),
+            )
+        }
+        None => (query, None),
+    })
+}
+
+// bounds which split integers from `min` to `max` into at most `partitions` ranges.
+fn split_integers(min: i128, max: i128, partitions: u32) -> Vec<i128> {
+    let partitions = i128::from(partitions.max(1));
+    let mut bounds = (1..partitions)
+        .map(|idx| min + (max - min + 1) * idx / partitions)
+        .filter(|bound| *bound > min && *bound <= max)
+        .collect::<Vec<_>>();
+    bounds.dedup();
+    bounds
+}
+
+/// inner bounds which split values from `min` to `max` into at most `partitions` ranges of equal
+/// width, empty if there is one range only. Integers, decimals, doubles, dates and timestamps
+/// could be split, None for other types.
+pub fn split_range(
+    min: &SchemaTypeWithValue,
+    max: &SchemaTypeWithValue,
+    partitions: u32,
+) -> Option<Vec<SchemaTypeWithValue>> {
+    let micros = |t: &NaiveDateTime| i128::from(t.and_utc().timestamp_micros());
+    let from_micros = |micros: i128| DateTime::from_timestamp_micros(i64::try_from(micros).ok()?);
+    match (min, max) {
+        (SchemaTypeWithValue::None, _) | (_, SchemaTypeWithValue::None) => Some(vec![]),
+        (
+            SchemaTypeWithValue::Int32(_) | SchemaTypeWithValue::Int64(_),
+            SchemaTypeWithValue::Int32(_) | SchemaTypeWithValue::Int64(_),
+        ) => Some(
+            split_integers(
+                i128::from(min.as_i64()?),
+                i128::from(max.as_i64()?),
+                partitions,
+            )
+            .into_iter()
+            .map(|bound| Some(SchemaTypeWithValue::Int64(i64::try_from(bound).ok()?)))
+            .collect::<Option<Vec<_>>>()?,
+        ),
+        // integral decimals, e.g. `bigint unsigned` of mysql, are split exactly.
+        (SchemaTypeWithValue::Decimal(min), SchemaTypeWithValue::Decimal(max))
+            if min.is_integer() && max.is_integer() =>
+        {
+            // conversions of BigDecimal are by i64, integers of BigInt are taken instead.
+            let integer = |d: &BigDecimal| d.with_scale(0).into_bigint_and_exponent().0.to_i128();
+            Some(
+                split_integers(integer(min)?, integer(max)?, partitions)
+                    .into_iter()
+                    .map(|bound| {
+                        SchemaTypeWithValue::Decimal(BigDecimal::from(BigInt::from(bound)))
+                    })
+                    .collect(),
+            )
+        }
+        (
+            SchemaTypeWithValue::Double(_)
+            | SchemaTypeWithValue::Float(_)
+            | SchemaTypeWithValue::Decimal(_),
+            SchemaTypeWithValue::Double(_)
+            | SchemaTypeWithValue::Float(_)
+            | SchemaTypeWithValue::Decimal(_),
+        ) => {
+            let (min, max) = (min.as_decimal()?.to_f64()?, max.as_decimal()?.to_f64()?);
+            let partitions = partitions.max(1);
+            let mut bounds = (1..partitions)
+                .map(|idx| min + (max - min) * f64::from(idx) / f64::from(partitions))
+                .filter(|bound| *bound > min && *bound <= max)
+                .collect::<Vec<_>>();
+            bounds.dedup();
+            Some(
+                bounds
+                    .into_iter()
+                    .map(SchemaTypeWithValue::Double)
+                    .collect(),
+            )
+        }
+        (SchemaTypeWithValue::Date(min), SchemaTypeWithValue::Date(max)) => split_integers(
+            i128::from(min.num_days_from_ce()),
+            i128::from(max.num_days_from_ce()),
+            partitions,
+        )
+        .into_iter()
+        .map(|days| {
+            NaiveDate::from_num_days_from_ce_opt(i32::try_from(days).ok()?)
+                .map(SchemaTypeWithValue::Date)
+        })
+        .collect(),
+        (SchemaTypeWithValue::Timestamp(min), SchemaTypeWithValue::Timestamp(max)) => {
+            split_integers(micros(min), micros(max), partitions)
+                .into_iter()
+                .map(|bound| {
+                    Some(SchemaTypeWithValue::Timestamp(
+                        from_micros(bound)?.naive_utc(),
+                    ))
+                })
+                .collect()
+        }
+        (SchemaTypeWithValue::Timestamptz(min), SchemaTypeWithValue::Timestamptz(max)) => {
+            split_integers(
+                micros(&min.naive_utc()),
+                micros(&max.naive_utc()),
+                partitions,
+            )
+            .into_iter()
+            .map(|bound| Some(SchemaTypeWithValue::Timestamptz(from_micros(bound)?)))
+            .collect()
+        }
+        _ => None,
+    }
+}
+
+/// literal of bound in sql, it's compared with the column which bound comes from.
+pub fn sql_literal(value: &SchemaTypeWithValue) -> Result<String> {
+    match value {
+        SchemaTypeWithValue::Int32(_)
+        | SchemaTypeWithValue::Int64(_)
+        | SchemaTypeWithValue::Double(_)
+        | SchemaTypeWithValue::Decimal(_) => Ok(value.to_text().unwrap_or_default()),
+        SchemaTypeWithValue::Date(d) => Ok(format!("'{}'", d.format("%Y-%m-%d"))),
+        SchemaTypeWithValue::Timestamp(t) => Ok(format!("'{}'", t.format("%Y-%m-%d %H:%M:%S%.6f"))),
+        SchemaTypeWithValue::Timestamptz(t) => {
+            Ok(format!("'{}'", t.format("%Y-%m-%d %H:%M:%S%.6f+00:00")))
+        }
+        unk => Err(
+            ParameterError::new(format!("cannot use {unk:?} as bound of range").as_str()).into(),
+        ),
+    }
+}
+
+/// `query` split by `bounds` of `column`(quoted) into ranges, nulls of column are in the first
+/// one and the last one has no upper bound.
+pub fn partition_queries(
+    query: &str,
+    column: &str,
+    bounds: &[SchemaTypeWithValue],
+) -> Result<Vec<String>> {
+    if bounds.is_empty() {
+        return Ok(vec![query.to_string()]);
+    }
+    let bounds = bounds.iter().map(sql_literal).collect::<Result<Vec<_>>>()?;
+    let mut conditions = vec![format!("{column} < {} or {column} is null", bounds[0])];
+    for range in bounds.windows(2) {
+        conditions.push(format!(
+            "{column} >= {} and {column} < {}",
+            range[0], range[1]
+        ));
+    }
+    conditions.push(format!("{column} >= {}", bounds[bounds.len() - 1]));
+    Ok(conditions
+        .into_iter()
+        .map(|condition| {
+            format!("select * from ({query}) as datawhirr_partition where {condition}")
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SchemaTypeWithValue as V;
+
+    #[test]
+    fn split_range_into_partitions() {
+        let date = |day| V::Date(NaiveDate::from_ymd_opt(2024, 1, day).unwrap());
+        let at =
+            |micros| V::Timestamp(DateTime::from_timestamp_micros(micros).unwrap().naive_utc());
+        for (min, max, partitions, expected) in [
+            (
+                V::Int32(1),
+                V::Int64(100),
+                4,
+                Some(vec![V::Int64(26), V::Int64(51), V::Int64(76)]),
+            ),
+            // ranges are never empty, so there are fewer of them than partitions.
+            (V::Int64(1), V::Int64(2), 4, Some(vec![V::Int64(2)])),
+            (V::Int64(5), V::Int64(5), 4, Some(vec![])),
+            (V::Int64(1), V::Int64(100), 0, Some(vec![])),
+            (V::None, V::Int64(100), 4, Some(vec![])),
+            (
+                V::Decimal(BigDecimal::from(u64::MAX - 9)),
+                V::Decimal(BigDecimal::from(u64::MAX)),
+                2,
+                Some(vec![V::Decimal(BigDecimal::from(u64::MAX - 4))]),
+            ),
+            (
+                V::Double(0.0),
+                V::Double(1.0),
+                4,
+                Some(vec![V::Double(0.25), V::Double(0.5), V::Double(0.75)]),
+            ),
+            (date(1), date(10), 3, Some(vec![date(4), date(7)])),
+            (at(0), at(9), 2, Some(vec![at(5)])),
+            (
+                V::String("a".to_string()),
+                V::String("z".to_string()),
+                4,
+                None,
+            ),
+        ] {
+            assert_eq!(
+                split_range(&min, &max, partitions),
+                expected,
+                "{min:?} to {max:?} in {partitions}"
+            );
+        }
+    }
+
+    #[test]
+    fn partition_queries_by_bounds() {
+        assert_eq!(
+            partition_queries("select * from t", r#""id""#, &[]).unwrap(),
+            vec!["select * from t"]
+        );
+        assert_eq!(
+            partition_queries("select * from t", r#""id""#, &[V::Int64(10), V::Int64(20)]).unwrap(),
+            vec![
+                r#"select * from (select * from t) as datawhirr_partition where "id" < 10 or "id" is null"#,
+                r#"select * from (select * from t) as datawhirr_partition where "id" >= 10 and "id" < 20"#,
+                r#"select * from (select * from t) as datawhirr_partition where "id" >= 20"#,
+            ]
+        );
+        assert!(partition_queries("select * from t", "id", &[V::Boolean(true)]).is_err());
+    }
+
+    #[test]
+    fn merge_schema_keeps_column_order() {
+        let schema = |names: &[&str]| {
+            Schema(
+                names
+                    .iter()
+                    .map(|name| SchemaField {
+                        name: name.to_string(),
+                        type_: SchemaType::Int64,
+                        extra: HashMap::new(),
+                    })
+                    .collect(),
+            )
+        };
+        let merged = merge_schema(&schema(&["b", "a"]), &schema(&["z", "a", "y", "c", "x"]));
+        assert_eq!(
+            merged
+                .0
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b", "a", "z", "y", "c", "x"]
+        );
+    }
+}
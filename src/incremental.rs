@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use bigdecimal::BigDecimal;
+use chrono::{Days, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::{
+    checkpoint::TypedValue,
+    data_storages::data_storages::{Row, SchemaTypeWithValue},
+    utils::{load_json, save_json},
+};
+
+/// watermark of incremental trans, the max value of `column` which has been transferred.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IncrementalState {
+    fingerprint: String,
+    column: String,
+    watermark: TypedValue,
+}
+
+/// watermark saved in state file `path`, None if there is no state yet. State of another trans is
+/// refused.
+pub fn load_watermark(
+    path: &str,
+    column: &str,
+    fingerprint: &str,
+) -> Result<Option<SchemaTypeWithValue>> {
+    if !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    let state: IncrementalState = load_json(path)?;
+    if state.fingerprint != fingerprint || state.column != column {
+        return Err(anyhow!(
+            "state is of another trans, source, sink, options or incremental column differ"
+        ));
+    }
+    Ok(Some(state.watermark.value()?))
+}
+
+pub fn save_watermark(
+    path: &str,
+    column: &str,
+    fingerprint: &str,
+    watermark: &SchemaTypeWithValue,
+) -> Result<()> {
+    save_json(
+        &IncrementalState {
+            fingerprint: fingerprint.to_string(),
+            column: column.to_string(),
+            watermark: TypedValue::new(watermark),
+        },
+        path,
+    )
+}
+
+/// the greater one of values, nulls and values which cannot be compared with `a` are skipped.
+pub fn max_value(
+    a: Option<SchemaTypeWithValue>,
+    b: Option<SchemaTypeWithValue>,
+) -> Option<SchemaTypeWithValue> {
+    match (a, b) {
+        (Some(a), Some(b)) if a.compare(&b).is_some_and(|order| order.is_lt()) => Some(b),
+        (None | Some(SchemaTypeWithValue::None), b) => b,
+        (a, _) => a,
+    }
+}
+
+/// max of `current` and values of `column` in rows.
+pub fn max_watermark(
+    current: Option<SchemaTypeWithValue>,
+    rows: &[Row],
+    column: &str,
+) -> Option<SchemaTypeWithValue> {
+    rows.iter()
+        .filter_map(|row| row.get(column))
+        .fold(current, |max, value| max_value(max, Some(value.clone())))
+}
+
+/// watermark moved back by `lookback` to read late updates again, it's seconds for timestamps,
+/// days for dates and the unit of column for numbers.
+pub fn apply_lookback(
+    watermark: SchemaTypeWithValue,
+    lookback: f64,
+) -> Result<SchemaTypeWithValue> {
+    if lookback == 0.0 {
+        return Ok(watermark);
+    }
+    let invalid = || anyhow!("invalid lookback {lookback} for watermark {watermark:?}");
+    let delta = TimeDelta::microseconds((lookback * 1e6) as i64);
+    Ok(match &watermark {
+        SchemaTypeWithValue::Int32(_) | SchemaTypeWithValue::Int64(_) => {
+            SchemaTypeWithValue::Int64(
+                watermark
+                    .as_i64()
+                    .and_then(|i| i.checked_sub(lookback.ceil() as i64))
+                    .ok_or_else(invalid)?,
+            )
+        }
+        SchemaTypeWithValue::Double(_)
+        | SchemaTypeWithValue::Float(_)
+        | SchemaTypeWithValue::Decimal(_) => SchemaTypeWithValue::Decimal(
+            watermark.as_decimal().ok_or_else(invalid)?
+                - BigDecimal::from_str(&lookback.to_string())?,
+        ),
+        SchemaTypeWithValue::Date(d) => SchemaTypeWithValue::Date(
+            d.checked_sub_days(Days::new(lookback.ceil() as u64))
+                .ok_or_else(invalid)?,
+        ),
+        SchemaTypeWithValue::Timestamp(t) => {
+            SchemaTypeWithValue::Timestamp(t.checked_sub_signed(delta).ok_or_else(invalid)?)
+        }
+        SchemaTypeWithValue::Timestamptz(t) => {
+            SchemaTypeWithValue::Timestamptz(t.checked_sub_signed(delta).ok_or_else(invalid)?)
+        }
+        _ => return Err(invalid()),
+    })
+}
@@ -1,16 +1,22 @@
 use core::panic;
-use std::{collections::HashMap, str::FromStr};
+use std::collections::HashMap;
 mod config;
 mod data_storages;
 use data_storages::{
-    data_storages::{ReadResult, Schema, SchemaTypeWithValue},
+    data_storages::{CoercionPolicy, ReadResult, Row, Schema, SchemaTypeWithValue},
+    shared::SharedStorage,
     DataStorage,
 };
 
-use clap::{command, Parser, Subcommand};
+use anyhow::{anyhow, Context};
+use async_channel::{Receiver, Sender};
+use clap::{Parser, Subcommand};
 use config::Config;
 use regex::Regex;
+mod checkpoint;
+mod incremental;
 mod utils;
+use checkpoint::{Ack, Checkpoint, PartCheckpoint};
 use utils::{new_chan, string_to_str_hashmap};
 
 #[derive(Parser, Debug, Clone)]
@@ -19,7 +25,7 @@ struct TransOptions {
     /// config path for source and sink.
     #[arg(short, long)]
     config: Option<String>,
-    /// source of data, could be name in config or a protocol.
+    /// source of data, could be name in config or a protocol, `-` means stdin.
     /// e.g. mysql://xxxx:xxxx/xxxx
     #[arg(long)]
     source: String,
@@ -30,25 +36,51 @@ struct TransOptions {
     /// options for sink, just like source_option.
     #[arg(long)]
     sink_option: Vec<String>,
-    /// schema for source.
+    /// schema for source, name of schema in config. It's used instead of schema read from source
+    /// and rows are cast to it after read.
     #[arg(long)]
     source_schema: Option<String>,
-    /// sink of data, could be name in config or a protocol, just like source.
+    /// sink of data, could be name in config or a protocol, `-` means stdout, just like source.
     #[arg(long)]
     sink: String,
-    /// schema for sink.
+    /// schema for sink, name of schema in config. Rows are reordered and cast to it before write.
     #[arg(long)]
     sink_schema: Option<String>,
+    /// how values are cast to sink schema, `strict`, `safe` or `lossy`, default `coercion` in config
+    /// or `safe`.
+    #[arg(long)]
+    coercion: Option<String>,
     /// if use chunk r/w, chunk size
     #[arg(long)]
     chunk_size: Option<u32>,
-    /// read buffer size(row number), if reads too fast and buffer size is too large may cause oom, 0 means
-    /// unbounded, default 0
+    /// read buffer size(row number) of chunk trans, rows read but not written yet are at most
+    /// buffer size rounded up to chunks. If reads too fast and buffer size is too large may cause
+    /// oom, 0 means unbounded, default 0
     #[arg(long, default_value_t = 0)]
     buffer_size: u32,
     /// number of thread, effect if set chunk_size, default 1
     #[arg(long, default_value_t = 1)]
     thread_number: u32,
+    /// file to save progress of chunk trans into after every written chunk, requires chunk_size
+    /// and a sink which persists every chunk, e.g. databases, csv or jsonl but not parquet.
+    #[arg(long)]
+    checkpoint: Option<String>,
+    /// resume chunk trans from checkpoint of the same job, file sinks are appended instead of
+    /// truncated.
+    #[arg(long, default_value_t = false)]
+    resume: bool,
+    /// column of source whose values only grow, e.g. `updated_at`. Only rows after the watermark
+    /// in state are read, supported by database sources, requires state.
+    #[arg(long)]
+    incremental_column: Option<String>,
+    /// file of watermark of incremental trans, it's the max value of incremental column read and
+    /// updated after the trans succeeds.
+    #[arg(long)]
+    state: Option<String>,
+    /// rows within lookback before watermark are read again for late updates, seconds for
+    /// timestamps, days for dates and the unit of column for numbers, default 0.
+    #[arg(long, default_value_t = 0.0)]
+    lookback: f64,
 }
 
 #[derive(Parser, Debug)]
@@ -59,10 +91,32 @@ struct GenOptions {
     output: String,
 }
 
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct SchemaOptions {
+    /// config path for source.
+    #[arg(short, long)]
+    config: Option<String>,
+    /// source of data, just like source of trans.
+    #[arg(long)]
+    source: String,
+    /// options for source, e.g. --source-option infer_schema=true for csv.
+    #[arg(long)]
+    source_option: Vec<String>,
+    /// name of schema in output.
+    #[arg(long, default_value_t = {"inferred".to_string()})]
+    name: String,
+    /// output file path, `-` means stdout.
+    #[arg(long, default_value_t = {"-".to_string()})]
+    output: String,
+}
+
 #[derive(Subcommand)]
 enum Subcommands {
     /// transfer data from source to sink.
     Trans(TransOptions),
+    /// read schema of source and write it as a `schemas` entry of config.
+    Schema(SchemaOptions),
     /// generate example config file.
     GenExample(GenOptions),
 }
@@ -78,27 +132,42 @@ struct Cli {
 fn convert_option(config: Vec<String>) -> HashMap<String, String> {
     config
         .into_iter()
-        .map(|each| {
-            if !each.contains('=') {
-                panic!("please specific config in format: 'k=v'.");
-            }
-            let mut split = each
-                .splitn(2, '=')
-                .map(String::from_str)
-                .map(|s| s.unwrap())
-                .collect::<Vec<_>>();
-            (split.swap_remove(0), split.swap_remove(1))
+        .map(|each| match each.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => panic!("please specific config in format: 'k=v'."),
         })
         .collect::<HashMap<_, _>>()
 }
 
+fn load_config(config_path: Option<String>) -> Option<Config> {
+    config_path.map(|config_path| {
+        let f = std::fs::File::open(&config_path)
+            .unwrap_or_else(|_| panic!("cannot open file {config_path}"));
+        serde_yaml::from_reader(f).unwrap()
+    })
+}
+
+// schema named `name` in config.
+fn config_schema(config: &Option<Config>, name: &str) -> Schema {
+    config
+        .as_ref()
+        .expect("must provide a config file if provided a schema name.")
+        .schemas
+        .get(name)
+        .unwrap_or_else(|| panic!("cannot find schema {name} in config file."))
+        .clone()
+}
+
 async fn load_data_storage(
     uri_or_name: &str,
     config: &Option<Config>,
     config_from_args: &HashMap<String, String>,
 ) -> Box<dyn data_storages::DataStorage + Send> {
     let r = Regex::new(r"[a-zA-Z0-9]+://.*").unwrap();
-    if r.is_match(uri_or_name) {
+    // `-` means standard streams, the format is decided by `format` in options.
+    if uri_or_name == "-" {
+        data_storages::loader::load_data_storage("stdio://", config_from_args).await
+    } else if r.is_match(uri_or_name) {
         data_storages::loader::load_data_storage(uri_or_name, config_from_args).await
     } else {
         match config {
@@ -119,115 +188,539 @@ async fn load_data_storage(
     }
 }
 
+// rows normalized to `schema` with the number of lossy casts, `offset` is the number of rows read
+// before them.
+fn normalize_rows(
+    data: Vec<Row>,
+    schema: &Schema,
+    policy: CoercionPolicy,
+    offset: usize,
+) -> anyhow::Result<(Vec<Row>, usize)> {
+    let mut lossy = 0;
+    let rows = data
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let (row, row_lossy) = row
+                .normalize(schema, policy)
+                .map_err(|err| err.at_row(offset + idx))?;
+            lossy += row_lossy;
+            Ok(row)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((rows, lossy))
+}
+
+/// schemas which rows are cast to after read, source schema first then sink schema.
+#[derive(Clone)]
+struct Normalize {
+    source_schema: Option<Schema>,
+    sink_schema: Option<Schema>,
+    policy: CoercionPolicy,
+}
+
+impl Normalize {
+    // result with rows normalized and schema of the last one applied, with the number of lossy
+    // casts. `offset` is the number of rows read before them.
+    fn apply(&self, mut res: ReadResult, offset: usize) -> anyhow::Result<(ReadResult, usize)> {
+        let mut lossy = 0;
+        for schema in [&self.source_schema, &self.sink_schema]
+            .into_iter()
+            .flatten()
+        {
+            let (data, data_lossy) = normalize_rows(res.data, schema, self.policy, offset)
+                .context("cannot normalize rows to schema")?;
+            res.data = data;
+            res.schema = schema.clone();
+            lossy += data_lossy;
+        }
+        Ok((res, lossy))
+    }
+}
+
+// fingerprint of what a trans reads and writes, checkpoint of other jobs cannot be resumed. Options
+// which don't change the rows, e.g. `append`, are not part of it.
+fn trans_fingerprint(
+    args: &TransOptions,
+    src_options: &HashMap<String, String>,
+    sink_options: &HashMap<String, String>,
+) -> String {
+    let sorted_options = |options: &HashMap<String, String>| {
+        let mut options = options
+            .iter()
+            .filter(|(key, _)| key.as_str() != "append")
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>();
+        options.sort();
+        options.join("\n")
+    };
+    checkpoint::fingerprint(&[
+        args.source.as_str(),
+        sorted_options(src_options).as_str(),
+        args.source_schema.as_deref().unwrap_or_default(),
+        args.sink.as_str(),
+        sorted_options(sink_options).as_str(),
+        args.sink_schema.as_deref().unwrap_or_default(),
+        args.incremental_column.as_deref().unwrap_or_default(),
+    ])
+}
+
+fn report_lossy(lossy: usize) {
+    if lossy > 0 {
+        eprintln!("warning: {lossy} values were rounded or truncated by lossy coercion");
+    }
+}
+
+/// chunk read from the `part`th part of source, `seq` is its number in the part.
+struct Chunk {
+    part: usize,
+    seq: u64,
+    res: ReadResult,
+}
+
+/// a part of source read from `cursor`, None to read from the beginning.
+struct SourcePart {
+    source: Box<dyn DataStorage + Send>,
+    options: HashMap<String, String>,
+    cursor: Option<SchemaTypeWithValue>,
+}
+
+// write each chunk from `r` into `sink` then close it, the channel is closed on error so the
+// reader and other writers stop. Written chunks are acknowledged to `acks` if set.
+async fn chunk_write(
+    mut sink: Box<dyn DataStorage + Send>,
+    r: Receiver<Chunk>,
+    schema: Option<Schema>,
+    sink_options: HashMap<String, String>,
+    acks: Option<Sender<Ack>>,
+) -> anyhow::Result<()> {
+    let sink_str_options = string_to_str_hashmap(&sink_options);
+    let result = async {
+        while let Ok(chunk) = r.recv().await {
+            sink.write(
+                chunk.res.data,
+                schema.clone().or(Some(chunk.res.schema)),
+                &sink_str_options,
+            )
+            .await?;
+            if let Some(acks) = &acks {
+                // the tracker has stopped on error, which is reported by itself.
+                acks.send(Ack {
+                    part: chunk.part,
+                    seq: chunk.seq,
+                    cursor: chunk.res.cursor,
+                })
+                .await
+                .ok();
+            }
+        }
+        sink.close().await
+    }
+    .await;
+    if result.is_err() {
+        r.close();
+    }
+    result
+}
+
+/// read chunks of one part of source and send them to writers, returns the source to commit, the
+/// number of lossy values and the max of `incremental_column` read. The channel is closed on
+/// error, which stops other parts and writers.
+async fn chunk_read_part(
+    part: usize,
+    source: SourcePart,
+    chunk_size: u32,
+    normalize: Normalize,
+    incremental_column: Option<String>,
+    s: Sender<Chunk>,
+) -> anyhow::Result<(
+    Box<dyn DataStorage + Send>,
+    usize,
+    Option<SchemaTypeWithValue>,
+)> {
+    let SourcePart {
+        mut source,
+        options,
+        mut cursor,
+    } = source;
+    let src_str_options = &string_to_str_hashmap(&options);
+    let mut lossy = 0;
+    let mut watermark = None;
+    let result = async {
+        let mut row_offset = 0;
+        for seq in 0.. {
+            let res = source
+                .chunk_read(cursor.clone(), chunk_size, src_str_options)
+                .await?;
+            if res.data.is_empty() {
+                return Ok(());
+            }
+            let read_rows = res.data.len();
+            if let Some(column) = &incremental_column {
+                watermark = incremental::max_watermark(watermark.take(), &res.data, column);
+            }
+            let (res, res_lossy) = normalize.apply(res, row_offset)?;
+            row_offset += read_rows;
+            lossy += res_lossy;
+            let next_cursor = res.cursor.clone();
+            // writers have closed the channel on error.
+            if s.send(Chunk { part, seq, res }).await.is_err() {
+                return Ok(());
+            }
+            match next_cursor {
+                None => return Ok(()),
+                Some(next) if cursor.as_ref().map(|c| c.to_json()) == Some(next.to_json()) => {
+                    return Err(anyhow!("cursor of source does not advance from {next:?}"));
+                }
+                next => cursor = next,
+            }
+        }
+        Ok(())
+    }
+    .await;
+    if result.is_err() {
+        s.close();
+    }
+    result.map(|_| (source, lossy, watermark))
+}
+
+// wait for all tasks, the first error of them if any.
+async fn join_tasks<T>(
+    tasks: Vec<tokio::task::JoinHandle<anyhow::Result<T>>>,
+) -> anyhow::Result<Vec<T>> {
+    let mut results = Vec::new();
+    let mut error = None;
+    for task in tasks {
+        match task.await.map_err(anyhow::Error::from).and_then(|r| r) {
+            Ok(result) => results.push(result),
+            Err(err) => {
+                error.get_or_insert(err);
+            }
+        }
+    }
+    match error {
+        Some(err) => Err(err),
+        None => Ok(results),
+    }
+}
+
+/// read chunks from parts of source concurrently and write them by a pool of sinks, one task
+/// each. The transfer stops at the first error of either side, its remaining chunks are dropped.
+/// Progress is saved into the checkpoint file after every written chunk if `checkpoint` is set,
+/// sources are committed after all chunks are written.
+/// At most `buffer_size` rows(rounded up to chunks, 0 for unbounded) wait for writers. Returns the
+/// max of `incremental_column` read.
+#[allow(clippy::too_many_arguments)]
 async fn chunk_trans(
     chunk_size: u32,
-    thread_num: u32,
-    sink_uri: String,
-    config: Option<Config>,
+    buffer_size: u32,
+    sinks: Vec<Box<dyn DataStorage + Send>>,
     sink_options: &HashMap<String, String>,
-    src_options: &HashMap<String, String>,
     schema: Option<Schema>,
-    mut source: Box<dyn DataStorage + Send>,
-) {
-    if thread_num == 0 {
-        panic!("thread number must genter than zero");
-    }
-    let (s, r) = new_chan::<ReadResult>(chunk_size);
-    let mut cursor: Option<SchemaTypeWithValue> = None;
-    let src_str_options = &string_to_str_hashmap(src_options);
-    let write_futures = (1..(thread_num + 1))
-        .map(|_| {
+    normalize: &Normalize,
+    sources: Vec<SourcePart>,
+    checkpoint: Option<(String, Checkpoint)>,
+    incremental_column: Option<&str>,
+) -> anyhow::Result<Option<SchemaTypeWithValue>> {
+    let (s, r) = new_chan::<Chunk>(buffer_size.div_ceil(chunk_size.max(1)));
+    let schema = normalize.sink_schema.clone().or(schema);
+    let (acks, track_task) = match checkpoint {
+        Some((path, checkpoint)) => {
+            let (acks, acks_r) = async_channel::unbounded();
             let r = r.clone();
-            let schema = schema.clone();
-            let config = config.clone();
-            let sink_options = sink_options.clone();
-            let sink_uri = sink_uri.clone();
-            tokio::spawn(async move {
-                let mut sink = load_data_storage(sink_uri.as_str(), &config, &sink_options).await;
-                while let Ok(res) = r.recv().await {
-                    sink.write(
-                        res.data,
-                        schema.clone().or(Some(res.schema)),
-                        &string_to_str_hashmap(&sink_options),
-                    )
-                    .await
-                    .expect("chunk sink error");
+            let track_task = tokio::spawn(async move {
+                let result = checkpoint::track(path, checkpoint, acks_r).await;
+                if result.is_err() {
+                    r.close();
                 }
-            })
+                result
+            });
+            (Some(acks), Some(track_task))
+        }
+        None => (None, None),
+    };
+    let write_tasks = sinks
+        .into_iter()
+        .map(|sink| {
+            tokio::spawn(chunk_write(
+                sink,
+                r.clone(),
+                schema.clone(),
+                sink_options.clone(),
+                acks.clone(),
+            ))
         })
         .collect::<Vec<_>>();
-    loop {
-        let res = source
-            .chunk_read(cursor, chunk_size, src_str_options)
-            .await
-            .expect("read from source error");
-        cursor = res.cursor.clone();
-        if res.data.is_empty() {
-            s.send(res).await.expect("cannot put result into chan");
-            break;
-        }
+    // the tracker stops once all writers are done.
+    drop(acks);
+    let read_tasks = sources
+        .into_iter()
+        .enumerate()
+        .map(|(part, source)| {
+            tokio::spawn(chunk_read_part(
+                part,
+                source,
+                chunk_size,
+                normalize.clone(),
+                incremental_column.map(str::to_string),
+                s.clone(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    // the channel is closed once all parts are read.
+    drop(s);
+    let read_result = join_tasks(read_tasks).await;
+    if read_result.is_err() {
+        // drop chunks not written yet.
+        while r.try_recv().is_ok() {}
     }
-    for write_future in write_futures {
-        write_future.await.expect("write error");
+    let write_result = join_tasks(write_tasks).await;
+    let track_result = join_tasks(track_task.into_iter().collect()).await;
+    let parts = read_result.context("read from source error")?;
+    write_result.context("write into sink error")?;
+    track_result.context("save checkpoint error")?;
+    let mut lossy = 0;
+    let mut watermark = None;
+    // rows are acknowledged after all of them are written.
+    for (mut source, part_lossy, part_watermark) in parts {
+        source.commit().await.context("commit source error")?;
+        lossy += part_lossy;
+        watermark = incremental::max_value(watermark, part_watermark);
     }
+    report_lossy(lossy);
+    Ok(watermark)
 }
 
 async fn exec_trans<'a: 'b, 'b>(args: TransOptions) {
-    let config: Option<Config> = match args.config {
-        Some(config_path) => {
-            let f = std::fs::File::open(config_path).expect("cannot open file {config_path}");
-            Some(serde_yaml::from_reader(f).unwrap())
+    let config = load_config(args.config.clone());
+    let source_schema = args
+        .source_schema
+        .as_ref()
+        .map(|name| config_schema(&config, name.as_str()));
+    let sink_schema = args
+        .sink_schema
+        .as_ref()
+        .map(|name| config_schema(&config, name.as_str()));
+    let policy = match &args.coercion {
+        Some(coercion) => coercion
+            .parse::<CoercionPolicy>()
+            .unwrap_or_else(|err| panic!("{err}")),
+        None => config
+            .as_ref()
+            .map_or(CoercionPolicy::default(), |config| config.coercion),
+    };
+    let mut src_options = convert_option(args.source_option.clone());
+    let mut sink_options = convert_option(args.sink_option.clone());
+    let fingerprint = trans_fingerprint(&args, &src_options, &sink_options);
+    if args.resume {
+        if args.checkpoint.is_none() {
+            panic!("resume requires checkpoint");
+        }
+        sink_options
+            .entry("append".to_string())
+            .or_insert("true".to_string());
+    }
+    if args.checkpoint.is_some() && args.chunk_size.is_none() {
+        panic!("checkpoint requires chunk size");
+    }
+    // sinks which convert values into their own types, e.g. arrow, cast them by the same policy.
+    sink_options
+        .entry("coercion".to_string())
+        .or_insert(policy.to_string());
+    // watermark of the last incremental trans, only rows after it are read.
+    let watermark = match (&args.incremental_column, &args.state) {
+        (None, _) => None,
+        (Some(_), None) => panic!("incremental column requires state"),
+        (Some(column), Some(path)) => {
+            let watermark = incremental::load_watermark(path, column, &fingerprint)
+                .unwrap_or_else(|err| panic!("cannot load state `{path}`: {err}"));
+            src_options.insert("incremental_column".to_string(), column.clone());
+            if let Some(watermark) = &watermark {
+                let bound = incremental::apply_lookback(watermark.clone(), args.lookback)
+                    .unwrap_or_else(|err| panic!("{err}"));
+                // the type of watermark is kept to bind it as a typed value.
+                src_options.insert("watermark".to_string(), bound.to_text().unwrap_or_default());
+                src_options.insert(
+                    "watermark_type".to_string(),
+                    serde_json::to_string(&bound.schema_type()).unwrap(),
+                );
+            }
+            watermark
         }
-        None => None,
     };
-    let src_options = convert_option(args.source_option);
-    let sink_options = convert_option(args.sink_option);
     let mut source = load_data_storage(args.source.as_str(), &config, &src_options).await;
+    if args.incremental_column.is_some() && !source.incremental() {
+        panic!(
+            "incremental column is not supported by source `{}`",
+            args.source
+        );
+    }
 
     let src_str_options = &string_to_str_hashmap(&src_options);
     // try read schema first
-    let schema = match source.read_schema(src_str_options).await {
-        Ok(schema) => Some(schema),
-        Err(err) => {
-            println!("may not support get schema, reason: {err}");
-            None
-        }
+    let schema = match &source_schema {
+        Some(source_schema) => Some(source_schema.clone()),
+        None => match source.read_schema(src_str_options).await {
+            Ok(schema) => Some(schema),
+            Err(err) => {
+                eprintln!("may not support get schema, reason: {err}");
+                None
+            }
+        },
+    };
+    let normalize = Normalize {
+        source_schema,
+        sink_schema,
+        policy,
     };
 
-    match args.chunk_size {
+    let read_watermark = match args.chunk_size {
         // chunk trans
         Some(chunk_size) => {
+            if args.thread_number == 0 {
+                panic!("thread number must genter than zero");
+            }
+            let sink = load_data_storage(args.sink.as_str(), &config, &sink_options).await;
+            if args.checkpoint.is_some() && !sink.resumable() {
+                panic!("sink `{}` cannot be resumed from checkpoint", args.sink);
+            }
+            let sinks = if sink.concurrent_write() {
+                let mut sinks = vec![sink];
+                for _ in 1..args.thread_number {
+                    sinks.push(load_data_storage(args.sink.as_str(), &config, &sink_options).await);
+                }
+                sinks
+            } else {
+                SharedStorage::split(sink, args.thread_number)
+                    .into_iter()
+                    .map(|sink| Box::new(sink) as Box<dyn DataStorage + Send>)
+                    .collect()
+            };
+            // parts of resumed trans are the saved ones, since partitions of source may change.
+            let (parts, checkpoint) = match (&args.checkpoint, args.resume) {
+                (Some(path), true) => {
+                    let checkpoint = Checkpoint::load(path)
+                        .unwrap_or_else(|err| panic!("cannot load checkpoint `{path}`: {err}"));
+                    if checkpoint.fingerprint != fingerprint {
+                        panic!("checkpoint `{path}` is of another trans, source, sink or options differ");
+                    }
+                    (checkpoint.parts.clone(), Some((path.clone(), checkpoint)))
+                }
+                (path, _) => {
+                    let parts = source
+                        .partition(src_str_options)
+                        .await
+                        .expect("partition source error")
+                        .into_iter()
+                        .map(PartCheckpoint::new)
+                        .collect::<Vec<_>>();
+                    let checkpoint = path.clone().map(|path| {
+                        (
+                            path,
+                            Checkpoint {
+                                fingerprint: fingerprint.clone(),
+                                parts: parts.clone(),
+                            },
+                        )
+                    });
+                    (parts, checkpoint)
+                }
+            };
+            // every part after the first is read by its own source.
+            let mut sources = Vec::new();
+            let mut source = Some(source);
+            for part in parts {
+                let part_source = match source.take() {
+                    Some(source) => source,
+                    None => load_data_storage(args.source.as_str(), &config, &part.options).await,
+                };
+                sources.push(SourcePart {
+                    source: part_source,
+                    cursor: part.cursor().expect("invalid checkpoint"),
+                    options: part.options,
+                });
+            }
             chunk_trans(
                 chunk_size,
-                args.thread_number,
-                args.sink.clone(),
-                config.clone(),
+                args.buffer_size,
+                sinks,
                 &sink_options,
-                &src_options,
                 schema,
-                source,
+                &normalize,
+                sources,
+                checkpoint,
+                args.incremental_column.as_deref(),
             )
-            .await;
+            .await
+            .expect("chunk trans error")
         }
-        // read all then write
+        // read (in parts if source streams) then write
         None => {
             let sink_str_options = &string_to_str_hashmap(&sink_options);
-            let source_read_res = source
-                .read(src_str_options)
-                .await
-                .expect("read from source error");
             let mut sink = load_data_storage(args.sink.as_str(), &config, &sink_options).await;
-            sink.write(
-                source_read_res.data,
-                Some(source_read_res.schema),
-                sink_str_options,
-            )
-            .await
-            .expect("write into sink error");
+            let mut row_offset = 0;
+            let mut lossy = 0;
+            let mut read_watermark = None;
+            loop {
+                let source_read_res = source
+                    .read(src_str_options)
+                    .await
+                    .expect("read from source error");
+                if let Some(column) = &args.incremental_column {
+                    read_watermark =
+                        incremental::max_watermark(read_watermark, &source_read_res.data, column);
+                }
+                let has_more = source_read_res.cursor.is_some();
+                let read_rows = source_read_res.data.len();
+                let (res, res_lossy) = normalize
+                    .apply(source_read_res, row_offset)
+                    .expect("read from source error");
+                row_offset += read_rows;
+                lossy += res_lossy;
+                sink.write(res.data, Some(res.schema), sink_str_options)
+                    .await
+                    .expect("write into sink error");
+                if !has_more {
+                    break;
+                }
+            }
+            sink.close().await.expect("close sink error");
+            // rows are acknowledged after all of them are written.
+            source.commit().await.expect("commit source error");
+            report_lossy(lossy);
+            read_watermark
+        }
+    };
+    if let (Some(column), Some(path)) = (&args.incremental_column, &args.state) {
+        if let Some(watermark) = incremental::max_value(watermark, read_watermark) {
+            incremental::save_watermark(path, column, &fingerprint, &watermark)
+                .unwrap_or_else(|err| panic!("cannot save state `{path}`: {err}"));
         }
     }
 }
 
+async fn exec_schema(args: SchemaOptions) {
+    let config = load_config(args.config);
+    let src_options = convert_option(args.source_option);
+    let mut source = load_data_storage(args.source.as_str(), &config, &src_options).await;
+    let schema = source
+        .read_schema(&string_to_str_hashmap(&src_options))
+        .await
+        .expect("read schema from source error");
+    let entry = HashMap::from([("schemas", HashMap::from([(args.name, schema)]))]);
+    if args.output == "-" {
+        serde_yaml::to_writer(std::io::stdout(), &entry).unwrap();
+    } else {
+        let f = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(args.output)
+            .expect("cannot open file");
+        serde_yaml::to_writer(f, &entry).unwrap();
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -235,6 +728,9 @@ async fn main() {
         Subcommands::Trans(args) => {
             exec_trans(args).await;
         }
+        Subcommands::Schema(args) => {
+            exec_schema(args).await;
+        }
         Subcommands::GenExample(args) => {
             let example = Config::example();
             let f = std::fs::OpenOptions::new()
@@ -247,3 +743,293 @@ async fn main() {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_storages::{
+        csv::CSVDataStorage,
+        data_storages::{Column, Row},
+        memory::MemoryStorage,
+    };
+    use std::time::Duration;
+
+    fn rows(n: i64) -> Vec<Row> {
+        (0..n)
+            .map(|id| {
+                Row(vec![Column {
+                    name: "id".to_string(),
+                    value: SchemaTypeWithValue::Int64(id),
+                }])
+            })
+            .collect()
+    }
+
+    fn ids(rows: &[Row]) -> Vec<i64> {
+        let mut ids = rows
+            .iter()
+            .map(|row| match row.get("id") {
+                Some(SchemaTypeWithValue::Int64(id)) => *id,
+                other => unreachable!("unexpected id {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    async fn partition(source: &MemoryStorage, partitions: usize) -> Vec<PartCheckpoint> {
+        let partitions = partitions.to_string();
+        source
+            .clone()
+            .partition(&HashMap::from([("partitions", partitions.as_str())]))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(PartCheckpoint::new)
+            .collect()
+    }
+
+    async fn trans(
+        source: MemoryStorage,
+        sink: &MemoryStorage,
+        thread_num: usize,
+        chunk_size: u32,
+        partitions: usize,
+    ) -> anyhow::Result<()> {
+        let parts = partition(&source, partitions).await;
+        trans_parts(source, parts, sink, thread_num, chunk_size, None).await
+    }
+
+    async fn trans_parts(
+        source: MemoryStorage,
+        parts: Vec<PartCheckpoint>,
+        sink: &MemoryStorage,
+        thread_num: usize,
+        chunk_size: u32,
+        checkpoint: Option<(String, Checkpoint)>,
+    ) -> anyhow::Result<()> {
+        let sinks = (0..thread_num)
+            .map(|_| Box::new(sink.clone()) as Box<dyn DataStorage + Send>)
+            .collect();
+        trans_sinks(
+            source,
+            parts,
+            sinks,
+            &HashMap::new(),
+            chunk_size,
+            checkpoint,
+        )
+        .await
+    }
+
+    async fn trans_sinks(
+        source: MemoryStorage,
+        parts: Vec<PartCheckpoint>,
+        sinks: Vec<Box<dyn DataStorage + Send>>,
+        sink_options: &HashMap<String, String>,
+        chunk_size: u32,
+        checkpoint: Option<(String, Checkpoint)>,
+    ) -> anyhow::Result<()> {
+        let sources = parts
+            .into_iter()
+            .map(|part| SourcePart {
+                source: Box::new(source.clone()),
+                cursor: part.cursor().unwrap(),
+                options: part.options,
+            })
+            .collect();
+        let normalize = Normalize {
+            source_schema: None,
+            sink_schema: None,
+            policy: CoercionPolicy::Safe,
+        };
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            chunk_trans(
+                chunk_size,
+                chunk_size,
+                sinks,
+                sink_options,
+                None,
+                &normalize,
+                sources,
+                checkpoint,
+                None,
+            ),
+        )
+        .await
+        .expect("chunk trans hangs")
+        .map(|_| ())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn chunk_trans_writes_every_chunk() {
+        let sink = MemoryStorage::default();
+        trans(MemoryStorage::new(rows(1000)), &sink, 4, 7, 1)
+            .await
+            .unwrap();
+        assert_eq!(ids(&sink.written()), (0..1000).collect::<Vec<_>>());
+        assert_eq!(sink.closed(), 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn chunk_trans_reads_every_partition() {
+        let sink = MemoryStorage::default();
+        let source = MemoryStorage::new(rows(1000));
+        trans(source.clone(), &sink, 3, 7, 4).await.unwrap();
+        assert_eq!(ids(&sink.written()), (0..1000).collect::<Vec<_>>());
+        assert_eq!(sink.closed(), 3);
+        assert_eq!(source.committed(), 4);
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("datawhirr-{name}-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    // writers of one csv file, which are shared like in `exec_trans`.
+    fn csv_sinks(path: &str, thread_num: u32) -> Vec<Box<dyn DataStorage + Send>> {
+        let sink = CSVDataStorage::new(format!("file+csv://{path}").as_str());
+        SharedStorage::split(Box::new(sink), thread_num)
+            .into_iter()
+            .map(|sink| Box::new(sink) as Box<dyn DataStorage + Send>)
+            .collect()
+    }
+
+    // ids in the csv file written by `csv_sinks`, the file is removed.
+    fn csv_ids(path: &str) -> Vec<i64> {
+        let written = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some("id"));
+        let mut ids = lines
+            .map(|line| line.parse::<i64>().unwrap())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn chunk_trans_shares_file_sink() {
+        let path = temp_path("shared.csv");
+        let source = MemoryStorage::new(rows(1000));
+        let parts = partition(&source, 4).await;
+        let sinks = csv_sinks(&path, 4);
+        trans_sinks(source, parts, sinks, &HashMap::new(), 7, None)
+            .await
+            .unwrap();
+        assert_eq!(csv_ids(&path), (0..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn chunk_trans_finishes_on_empty_source() {
+        let sink = MemoryStorage::default();
+        trans(MemoryStorage::new(vec![]), &sink, 2, 10, 2)
+            .await
+            .unwrap();
+        assert!(sink.written().is_empty());
+        assert_eq!(sink.closed(), 2);
+    }
+
+    #[tokio::test]
+    async fn chunk_trans_stops_on_read_error() {
+        let sink = MemoryStorage::default();
+        let mut source = MemoryStorage::new(rows(1000));
+        source.fail_read_at = Some(50);
+        let err = trans(source, &sink, 2, 10, 4).await.unwrap_err();
+        assert_eq!(err.to_string(), "read from source error");
+        assert!(sink.written().len() <= 50);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn chunk_trans_resumes_from_checkpoint() {
+        let path = temp_path("checkpoint.json");
+        let mut source = MemoryStorage::new(rows(1000));
+        let parts = partition(&source, 3).await;
+        let checkpoint = Checkpoint {
+            fingerprint: "test".to_string(),
+            parts: parts.clone(),
+        };
+        source.fail_read_at = Some(600);
+        let sink = MemoryStorage::default();
+        trans_parts(
+            source.clone(),
+            parts,
+            &sink,
+            3,
+            7,
+            Some((path.clone(), checkpoint)),
+        )
+        .await
+        .unwrap_err();
+
+        let checkpoint = Checkpoint::load(&path).unwrap();
+        source.fail_read_at = None;
+        let resumed_sink = MemoryStorage::default();
+        trans_parts(
+            source,
+            checkpoint.parts.clone(),
+            &resumed_sink,
+            2,
+            7,
+            Some((path.clone(), checkpoint)),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut written = sink.written();
+        written.extend(resumed_sink.written());
+        assert_eq!(ids(&written), (0..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn chunk_trans_resumes_into_file_sink() {
+        let path = temp_path("resumed.csv");
+        let checkpoint_path = temp_path("resumed-checkpoint.json");
+        let mut source = MemoryStorage::new(rows(1000));
+        let parts = partition(&source, 3).await;
+        let checkpoint = Checkpoint {
+            fingerprint: "test".to_string(),
+            parts: parts.clone(),
+        };
+        source.fail_read_at = Some(600);
+        trans_sinks(
+            source.clone(),
+            parts,
+            csv_sinks(&path, 3),
+            &HashMap::new(),
+            7,
+            Some((checkpoint_path.clone(), checkpoint)),
+        )
+        .await
+        .unwrap_err();
+
+        let checkpoint = Checkpoint::load(&checkpoint_path).unwrap();
+        source.fail_read_at = None;
+        trans_sinks(
+            source,
+            checkpoint.parts.clone(),
+            csv_sinks(&path, 2),
+            &HashMap::from([("append".to_string(), "true".to_string())]),
+            7,
+            Some((checkpoint_path.clone(), checkpoint)),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_file(&checkpoint_path).unwrap();
+        assert_eq!(csv_ids(&path), (0..1000).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn chunk_trans_stops_on_write_error() {
+        let mut sink = MemoryStorage::default();
+        sink.fail_write = true;
+        let source = MemoryStorage::new(rows(1000));
+        let err = trans(source.clone(), &sink, 3, 1, 1).await.unwrap_err();
+        assert_eq!(err.to_string(), "write into sink error");
+        assert!(sink.written().is_empty());
+        assert_eq!(source.committed(), 0);
+    }
+}
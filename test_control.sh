@@ -12,6 +12,16 @@ function startup_docker() {
 		-d postgres:16.3
 }
 
+# kafka of ignored tests, e.g. `cargo test -- --ignored kafka`.
+function startup_kafka() {
+	podman stop test_kafka
+	podman rm test_kafka
+	podman run -d \
+		-p 9092:9092 \
+		--name test_kafka \
+		-d apache/kafka:3.7.0
+}
+
 function insert_test_data() {
 	podman exec test_pg psql -Utest -c "$(
 		cat <<EOF
@@ -37,7 +47,10 @@ startup)
 insert)
 	insert_test_data
 	;;
+kafka)
+	startup_kafka
+	;;
 *)
-	echo "must specify a command in 'startup', 'insert'"
+	echo "must specify a command in 'startup', 'insert', 'kafka'"
 	;;
 esac